@@ -1,23 +1,185 @@
+use std::collections::HashMap;
+use std::path::{PathBuf, MAIN_SEPARATOR};
+
 use crossterm::event::KeyCode;
+use strum_macros::{Display, EnumString};
+
+use crate::diff;
+use crate::encoding::{self, FileEncoding};
+use crate::swap::{self, Swap};
+use crate::watch::{self, DiskState, FileWatcher};
 
-use crate::widgets::navigation::NavigationWindow;
+use crate::widgets::changed::{ChangedChoice, ChangedWindow, DiskContents};
+use crate::widgets::confirm::{ConfirmChoice, ConfirmWindow, PendingAction};
+use crate::widgets::explorer::ExplorerAction;
+use crate::widgets::file::FileWindow;
+use crate::widgets::finder::{FinderResult, FinderWindow};
+use crate::widgets::hex;
+use crate::widgets::navigation::{ActionMenuWindow, NavigationFocus, NavigationWindow};
+use crate::widgets::prompt::{PromptKind, PromptResult, PromptWindow};
+use crate::widgets::recover::{RecoverChoice, RecoverWindow};
 use crate::widgets::{file_manager::FileManagerWindow, Window};
 
-#[derive(PartialEq)]
+#[derive(PartialEq, Display)]
 pub enum WindowState {
     Editor,
     FileMenu,
     Navigation,
 }
 
+// How the gutter numbers lines. Relative numbers count from the cursor line,
+// which itself still shows its absolute number.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum LineNumbers {
+    Absolute,
+    Relative,
+    Off,
+}
+
+/// The pieces of information the status bar can show.
+#[derive(Clone, Copy, Debug, Display, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "kebab-case")]
+pub enum StatusItem {
+    Mode,
+    File,
+    Modified,
+    Position,
+    Lines,
+    LineEnding,
+    Encoding,
+    FileType,
+}
+
+/// The items shown in the status bar, in the order they are shown in.
+/// It is parsed from a comma separated list such as "mode,file,position".
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StatusBar {
+    pub items: Vec<StatusItem>,
+}
+
+impl Default for StatusBar {
+    fn default() -> StatusBar {
+        StatusBar {
+            items: vec![
+                StatusItem::Mode,
+                StatusItem::File,
+                StatusItem::Modified,
+                StatusItem::Position,
+                StatusItem::Lines,
+                StatusItem::LineEnding,
+                StatusItem::Encoding,
+                StatusItem::FileType,
+            ],
+        }
+    }
+}
+
+impl std::str::FromStr for StatusBar {
+    type Err = strum::ParseError;
+
+    fn from_str(s: &str) -> Result<StatusBar, strum::ParseError> {
+        let items = s
+            .split(',')
+            .map(str::trim)
+            .filter(|item| !item.is_empty())
+            .map(str::parse)
+            .collect::<Result<Vec<StatusItem>, _>>()?;
+
+        Ok(StatusBar { items })
+    }
+}
+
+/// A file named on the command line, with the position to open it at.
+/// Lines and columns count from 1, as they are shown.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FileTarget {
+    pub path: PathBuf,
+    pub line: Option<usize>,
+    pub column: Option<usize>,
+}
+
+impl FileTarget {
+    /// Parses the file arguments. A file can be followed by ":line" or
+    /// ":line:column", or preceded by a separate "+line" argument.
+    pub fn parse_args(args: &[String]) -> Vec<FileTarget> {
+        let mut targets = Vec::new();
+        let mut next_line = None;
+
+        for arg in args {
+            if let Some(line) = arg.strip_prefix('+').and_then(|n| n.parse().ok()) {
+                next_line = Some(line);
+                continue;
+            }
+
+            let mut target = FileTarget::parse(arg);
+            target.line = target.line.or(next_line.take());
+            targets.push(target);
+        }
+
+        targets
+    }
+
+    fn parse(arg: &str) -> FileTarget {
+        let whole = FileTarget {
+            path: PathBuf::from(arg),
+            line: None,
+            column: None,
+        };
+
+        // A file that exists with the whole name, colons included, is opened as it is.
+        if whole.path.exists() {
+            return whole;
+        }
+
+        let mut parts = arg.rsplitn(3, ':');
+        let numbers: Vec<Option<usize>> = parts.by_ref().take(2).map(|n| n.parse().ok()).collect();
+
+        match (numbers.as_slice(), parts.next()) {
+            ([Some(column), Some(line)], Some(path)) if !path.is_empty() => FileTarget {
+                path: PathBuf::from(path),
+                line: Some(*line),
+                column: Some(*column),
+            },
+            _ => match arg.rsplit_once(':') {
+                Some((path, line)) if !path.is_empty() && line.parse::<usize>().is_ok() => {
+                    FileTarget {
+                        path: PathBuf::from(path),
+                        line: line.parse().ok(),
+                        column: None,
+                    }
+                }
+                _ => whole,
+            },
+        }
+    }
+}
+
 pub struct Context {
     pub width: u16,
     pub height: u16,
 
     pub current_window: WindowState,
+    pub line_numbers: LineNumbers,
+    pub status_bar: StatusBar,
 
     pub file_manager_window: FileManagerWindow,
     pub navigation_window: NavigationWindow,
+    // While a confirm dialog or a prompt is open, it receives all key presses.
+    pub confirm_window: Option<ConfirmWindow>,
+    pub prompt_window: Option<PromptWindow>,
+    pub finder_window: Option<FinderWindow>,
+    pub recover_window: Option<RecoverWindow>,
+    pub changed_window: Option<ChangedWindow>,
+    // Swap files of unnamed files from editors that are gone, offered in turn.
+    pub unnamed_swaps: Vec<Swap>,
+    // Notices when another program changes an open file.
+    pub file_watcher: FileWatcher,
+    // The inputs submitted to each kind of prompt, oldest first.
+    pub prompt_history: HashMap<PromptKind, Vec<String>>,
+
+    // A message for the user, shown in the status bar until the next key press.
+    pub status_message: Option<String>,
 }
 
 pub struct App {
@@ -38,21 +200,31 @@ impl App {
                 height: 0,
 
                 current_window: WindowState::Editor,
+                line_numbers: LineNumbers::Absolute,
+                status_bar: StatusBar::default(),
 
                 file_manager_window: FileManagerWindow::new(),
                 navigation_window: NavigationWindow::new(),
+                confirm_window: None,
+                prompt_window: None,
+                finder_window: None,
+                recover_window: None,
+                changed_window: None,
+                unnamed_swaps: Vec::new(),
+                file_watcher: FileWatcher::new(),
+                prompt_history: HashMap::new(),
+                status_message: None,
             },
             // interaction_menu_visable: false,
         }
     }
 
-    pub fn on_key(&mut self, c: char) {
-        match c {
-            _ => {}
-        }
-    }
+    pub fn on_key(&mut self, _c: char) {}
 
     pub fn on_tick(&mut self) {
+        self.context.file_manager_window.write_swaps();
+        self.check_disk_changes(false);
+
         // Update progress
         // self.progress += 0.001;
         // if self.progress > 1.0 {
@@ -69,6 +241,979 @@ impl App {
         // self.barchart.insert(0, event);
     }
 
+    // Quitting asks about every modified file in turn, starting at from_index.
+    // The app only quits once none are left. An unnamed file that is piped
+    // to stdout is not asked about, as writing it to stdout saves it.
+    pub fn request_quit(&mut self, from_index: usize) {
+        let file_manager = &mut self.context.file_manager_window;
+        // Without a stdin buffer, the file current when quitting starts is piped.
+        if from_index == 0
+            && file_manager.stdout
+            && !file_manager.file_list.iter().any(|file| file.piped)
+        {
+            file_manager.current_file().piped = true;
+        }
+        let modified = (from_index..file_manager.file_list.len()).find(|i| {
+            let file = &file_manager.file_list[*i];
+            file.is_modified() && !(file.piped && file.path.is_none())
+        });
+
+        match modified {
+            Some(file_index) => {
+                file_manager.current_file_index = file_index;
+                let message = format!(
+                    "Save changes to {} before quitting?",
+                    file_manager.file_list[file_index].name.to_string_lossy()
+                );
+                self.context.confirm_window = Some(ConfirmWindow::new(
+                    message,
+                    PendingAction::Quit { file_index },
+                ));
+            }
+            None => {
+                self.context.file_manager_window.remove_swaps();
+                self.should_quit = true;
+            }
+        }
+    }
+
+    pub fn request_close(&mut self) {
+        let file_manager = &mut self.context.file_manager_window;
+        let file_index = file_manager.current_file_index;
+
+        if file_manager.current_file().is_modified() {
+            let message = format!(
+                "Save changes to {} before closing?",
+                file_manager.current_file().name.to_string_lossy()
+            );
+            self.context.confirm_window = Some(ConfirmWindow::new(
+                message,
+                PendingAction::CloseFile { file_index },
+            ));
+        } else {
+            file_manager.close_file(file_index);
+        }
+    }
+
+    // Acts on the answer of the confirm dialog, once the user has picked one.
+    fn handle_confirm_choice(&mut self) {
+        let (choice, action) = match &self.context.confirm_window {
+            Some(ConfirmWindow {
+                choice: Some(choice),
+                action,
+                ..
+            }) => (*choice, *action),
+            _ => return,
+        };
+
+        let file_index = match action {
+            PendingAction::Quit { file_index } | PendingAction::CloseFile { file_index } => {
+                file_index
+            }
+        };
+
+        match choice {
+            ConfirmChoice::Cancel => {
+                self.context.confirm_window = None;
+                return;
+            }
+            // A file without a path asks for one, and the action goes on once it is saved.
+            ConfirmChoice::Save
+                if self.context.file_manager_window.file_list[file_index]
+                    .path
+                    .is_none() =>
+            {
+                self.context.confirm_window = None;
+                self.prompt_save_as();
+                if let Some(prompt) = &mut self.context.prompt_window {
+                    prompt.pending_action = Some(action);
+                }
+                return;
+            }
+            ConfirmChoice::Save => {
+                let file = &mut self.context.file_manager_window.file_list[file_index];
+                if let Err(error) = file.save_file() {
+                    // The dialog stays open, so the user can discard or cancel instead.
+                    if let Some(confirm) = &mut self.context.confirm_window {
+                        confirm.error = Some(format!("Could not save: {}", error));
+                        confirm.choice = None;
+                    }
+                    return;
+                }
+            }
+            ConfirmChoice::Discard => {}
+        }
+
+        self.context.confirm_window = None;
+        self.continue_action(action);
+    }
+
+    // Goes on with quitting or closing, once the file it waited on is saved or discarded.
+    fn continue_action(&mut self, action: PendingAction) {
+        match action {
+            PendingAction::Quit { file_index } => self.request_quit(file_index + 1),
+            PendingAction::CloseFile { file_index } => {
+                self.context.file_manager_window.close_file(file_index)
+            }
+        }
+    }
+
+    // Performs an action picked in the navigation menu.
+    pub fn run_action(&mut self, action: ActionMenuWindow) {
+        self.context.current_window = WindowState::Editor;
+        let file_manager = &mut self.context.file_manager_window;
+
+        // Line endings and encodings belong to text, which the hex view does not have.
+        let text_only = matches!(
+            action,
+            ActionMenuWindow::ConvertLineEndings
+                | ActionMenuWindow::ReopenWithEncoding
+                | ActionMenuWindow::SaveWithEncoding
+        );
+        if text_only && file_manager.current_file().hex.is_some() {
+            self.context.status_message = Some("The hex view shows bytes, not text".to_string());
+            return;
+        }
+
+        match action {
+            ActionMenuWindow::NewFile => {
+                file_manager.add_file_menu(FileWindow::new("untitled"));
+                file_manager.current_file_index = file_manager.file_list.len() - 1;
+            }
+            ActionMenuWindow::OpenFile => self.open_prompt(PromptKind::OpenFile, "Open file", ""),
+            ActionMenuWindow::SaveFile => self.save_current_file(),
+            ActionMenuWindow::SaveFileAs => self.prompt_save_as(),
+            ActionMenuWindow::SaveAll => self.save_all_files(),
+            ActionMenuWindow::CloseFile => self.request_close(),
+            ActionMenuWindow::ConvertLineEndings => {
+                let current = file_manager.current_file().line_ending;
+                self.open_prompt(
+                    PromptKind::LineEnding,
+                    &format!("Convert {} line endings to (lf, crlf, cr)", current),
+                    "",
+                );
+            }
+            ActionMenuWindow::ReopenWithEncoding => {
+                self.open_prompt(PromptKind::ReopenWithEncoding, "Reopen with encoding", "")
+            }
+            ActionMenuWindow::SaveWithEncoding => {
+                let current = file_manager.current_file().encoding.to_string();
+                self.open_prompt(PromptKind::SaveWithEncoding, "Save with encoding", &current);
+            }
+            ActionMenuWindow::ToggleHexView => {
+                let file = file_manager.current_file();
+                self.context.status_message = if file.is_modified() {
+                    Some("Save or undo the changes before switching views".to_string())
+                } else {
+                    match file.toggle_hex_view() {
+                        Ok(()) if file.hex.is_some() => Some("Showing the bytes".to_string()),
+                        Ok(()) => Some("Showing the text".to_string()),
+                        Err(error) => Some(format!("Could not switch views: {}", error)),
+                    }
+                };
+            }
+        }
+    }
+
+    // Saving a file without a path asks for one first.
+    // A file that another program changed asks what to do with that first.
+    fn save_current_file(&mut self) {
+        let file = self.context.file_manager_window.current_file();
+        if file.path.is_none() {
+            self.prompt_save_as();
+            return;
+        }
+        if self.check_disk_changes(true) {
+            return;
+        }
+
+        let file = self.context.file_manager_window.current_file();
+
+        self.context.status_message = match file.save_file() {
+            Ok(()) => Some(format!("Saved {}", file.name.to_string_lossy())),
+            Err(error) => Some(format!("Could not save: {}", error)),
+        };
+    }
+
+    fn prompt_save_as(&mut self) {
+        let file = self.context.file_manager_window.current_file();
+        let path = file
+            .path
+            .as_ref()
+            .map(|path| path.display().to_string())
+            .unwrap_or_default();
+
+        self.open_prompt(PromptKind::SaveFileAs, "Save as", &path);
+    }
+
+    pub fn open_prompt(&mut self, kind: PromptKind, label: &str, input: &str) {
+        let history = self
+            .context
+            .prompt_history
+            .get(&kind)
+            .cloned()
+            .unwrap_or_default();
+
+        let mut prompt = PromptWindow::new(kind, label, input, history);
+        prompt.file_index = self.context.file_manager_window.current_file_index;
+        self.context.prompt_window = Some(prompt);
+    }
+
+    // Saves every modified file that has a path. Files without one are skipped,
+    // as there is no single prompt that could name them all.
+    fn save_all_files(&mut self) {
+        if self.check_disk_changes(true) {
+            return;
+        }
+
+        let mut saved = 0;
+        let mut errors = Vec::new();
+
+        for file in self.context.file_manager_window.file_list.iter_mut() {
+            if file.path.is_none() || !file.is_modified() {
+                continue;
+            }
+
+            match file.save_file() {
+                Ok(()) => saved += 1,
+                Err(error) => errors.push(format!("{}: {}", file.name.to_string_lossy(), error)),
+            }
+        }
+
+        self.context.status_message = if errors.is_empty() {
+            Some(format!("Saved {} files", saved))
+        } else {
+            Some(format!("Could not save {}", errors.join(", ")))
+        };
+    }
+
+    // Performs an action picked in the file explorer.
+    pub fn run_explorer_action(&mut self, action: ExplorerAction) {
+        match action {
+            ExplorerAction::Open(path) => {
+                let file_manager = &mut self.context.file_manager_window;
+                match file_manager.open_file(path.into()) {
+                    Ok(()) => self.context.current_window = WindowState::Editor,
+                    Err(error) => {
+                        self.context.status_message = Some(format!("Could not open: {}", error))
+                    }
+                }
+            }
+            ExplorerAction::NewFile(dir) => {
+                let input = format!("{}{}", dir.display(), MAIN_SEPARATOR);
+                self.open_prompt(PromptKind::NewFile, "New file", &input);
+            }
+            ExplorerAction::NewFolder(dir) => {
+                let input = format!("{}{}", dir.display(), MAIN_SEPARATOR);
+                self.open_prompt(PromptKind::NewFolder, "New folder", &input);
+            }
+            ExplorerAction::Rename(path) => {
+                self.open_prompt(PromptKind::Rename, "Rename to", &path.display().to_string());
+                if let Some(prompt) = &mut self.context.prompt_window {
+                    prompt.target = Some(path);
+                }
+            }
+            ExplorerAction::Delete(path) => {
+                let label = format!("Delete {}? (y/n)", path.display());
+                self.open_prompt(PromptKind::Delete, &label, "");
+                if let Some(prompt) = &mut self.context.prompt_window {
+                    prompt.target = Some(path);
+                }
+            }
+            ExplorerAction::RevealCurrentFile => self.reveal_current_file(),
+        }
+    }
+
+    // Selects the current file in the explorer, expanding the directories above it.
+    pub fn reveal_current_file(&mut self) {
+        let path = match &self.context.file_manager_window.current_file().path {
+            Some(path) => path.clone(),
+            None => {
+                self.context.status_message = Some("The file has no path".to_string());
+                return;
+            }
+        };
+
+        let explorer = &mut self.context.navigation_window.explorer;
+        if !explorer.reveal(&path) {
+            self.context.status_message = Some(format!(
+                "{} is not under {}",
+                path.display(),
+                explorer.root.display()
+            ));
+        }
+    }
+
+    // Creates, renames or deletes a file or folder for the explorer prompts.
+    // Open files follow a rename, so they are saved to the new path.
+    fn change_files(&mut self, kind: PromptKind, input: &str, target: Option<PathBuf>) {
+        let path = PathBuf::from(input);
+        let result = match (kind, target) {
+            (PromptKind::NewFile, _) => std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&path)
+                .map(|_| ()),
+            (PromptKind::NewFolder, _) => std::fs::create_dir_all(&path),
+            (PromptKind::Rename, Some(target)) => {
+                // The open files may have relative paths, so the paths are compared
+                // resolved, while the target still exists.
+                let target = watch::watched_path(&target).unwrap_or(target);
+                let file_list = &mut self.context.file_manager_window.file_list;
+                let resolved: Vec<Option<PathBuf>> = file_list
+                    .iter()
+                    .map(|file| watch::watched_path(file.path.as_deref()?))
+                    .collect();
+
+                let renamed = std::fs::rename(&target, &path);
+                if renamed.is_ok() {
+                    for (file, file_path) in file_list.iter_mut().zip(resolved) {
+                        let moved = file_path
+                            .as_deref()
+                            .and_then(|file_path| file_path.strip_prefix(&target).ok());
+                        if let Some(rest) = moved {
+                            let new_path = match rest.as_os_str().is_empty() {
+                                true => path.clone(),
+                                false => path.join(rest),
+                            };
+                            file.name = new_path.file_name().unwrap_or_default().to_os_string();
+                            file.path = Some(new_path.into_boxed_path());
+                        }
+                    }
+                }
+                renamed
+            }
+            (PromptKind::Delete, Some(target)) => {
+                if !matches!(input, "y" | "yes") {
+                    return;
+                }
+                if target.is_dir() {
+                    std::fs::remove_dir_all(&target)
+                } else {
+                    std::fs::remove_file(&target)
+                }
+            }
+            _ => return,
+        };
+
+        let explorer = &mut self.context.navigation_window.explorer;
+        explorer.refresh();
+
+        match result {
+            Ok(()) if kind == PromptKind::NewFile => {
+                explorer.reveal(&path);
+                self.run_explorer_action(ExplorerAction::Open(path));
+            }
+            Ok(()) => {
+                explorer.reveal(&path);
+            }
+            Err(error) => {
+                self.context.status_message = Some(format!("Could not change files: {}", error))
+            }
+        }
+    }
+
+    // Acts on the text entered in the prompt, once it is submitted or cancelled.
+    fn handle_prompt_result(&mut self) {
+        let (kind, input, target, file_index, pending_action) = match &self.context.prompt_window {
+            Some(PromptWindow {
+                kind,
+                result: Some(PromptResult::Submitted(input)),
+                target,
+                file_index,
+                pending_action,
+                ..
+            }) => (
+                *kind,
+                input.clone(),
+                target.clone(),
+                *file_index,
+                *pending_action,
+            ),
+            Some(PromptWindow {
+                result: Some(PromptResult::Cancelled),
+                ..
+            }) => {
+                self.context.prompt_window = None;
+                return;
+            }
+            _ => return,
+        };
+
+        self.context.prompt_window = None;
+        if input.is_empty() {
+            return;
+        }
+
+        // Submitting an input again moves it to the end of the history.
+        let history = self.context.prompt_history.entry(kind).or_default();
+        history.retain(|entry| *entry != input);
+        history.push(input.clone());
+
+        // The answer is about the file the prompt was opened for,
+        // even if another file became current in the meantime.
+        let file_manager = &mut self.context.file_manager_window;
+        if file_index < file_manager.file_list.len() {
+            file_manager.current_file_index = file_index;
+        }
+
+        match kind {
+            PromptKind::OpenFile => {
+                if let Err(error) = file_manager.open_file(PathBuf::from(input).into()) {
+                    self.context.status_message = Some(format!("Could not open: {}", error));
+                }
+            }
+            PromptKind::SaveFileAs => {
+                let file = file_manager.current_file();
+                match file.save_as_file(PathBuf::from(input).into()) {
+                    Ok(()) => {
+                        self.context.status_message =
+                            Some(format!("Saved {}", file.name.to_string_lossy()));
+                        if let Some(action) = pending_action {
+                            self.continue_action(action);
+                        }
+                    }
+                    Err(error) => {
+                        self.context.status_message = Some(format!("Could not save: {}", error))
+                    }
+                }
+            }
+            PromptKind::GoToLine => {
+                // The input is a line, optionally followed by ":column".
+                let mut numbers = input.split(':').map(|n| n.trim().parse::<usize>());
+                match (numbers.next(), numbers.next()) {
+                    (Some(Ok(line)), None) => file_manager.current_file().go_to(line, 1),
+                    (Some(Ok(line)), Some(Ok(column))) => {
+                        file_manager.current_file().go_to(line, column)
+                    }
+                    _ => self.context.status_message = Some(format!("Not a line: {}", input)),
+                }
+            }
+            PromptKind::Find => {
+                if !file_manager.current_file().find(&input) {
+                    self.context.status_message = Some(format!("Not found: {}", input));
+                }
+            }
+            PromptKind::LineEnding => match input.trim().parse() {
+                Ok(line_ending) => {
+                    let file = file_manager.current_file();
+                    file.convert_line_endings(line_ending);
+                    self.context.status_message =
+                        Some(format!("Converted line endings to {}", line_ending));
+                }
+                Err(error) => self.context.status_message = Some(error),
+            },
+            PromptKind::ReopenWithEncoding | PromptKind::SaveWithEncoding => match input.parse() {
+                Ok(encoding) => self.change_encoding(kind, encoding),
+                Err(error) => self.context.status_message = Some(error),
+            },
+            PromptKind::NewFile
+            | PromptKind::NewFolder
+            | PromptKind::Rename
+            | PromptKind::Delete => self.change_files(kind, &input, target),
+        }
+    }
+
+    // Reopens or saves the current file with another encoding.
+    // A file without a path keeps the encoding and asks for a path to save to.
+    // Saving checks the file on disk first, like any other save.
+    fn change_encoding(&mut self, kind: PromptKind, encoding: FileEncoding) {
+        let file = self.context.file_manager_window.current_file();
+
+        let result = match kind {
+            PromptKind::ReopenWithEncoding if file.is_modified() => {
+                self.context.status_message =
+                    Some("Save or undo the changes before reopening".to_string());
+                return;
+            }
+            PromptKind::ReopenWithEncoding => file.reopen_with_encoding(encoding),
+            _ if file.path.is_none() => {
+                file.encoding = encoding;
+                self.prompt_save_as();
+                return;
+            }
+            _ => {
+                if self.check_disk_changes(true) {
+                    return;
+                }
+                let file = self.context.file_manager_window.current_file();
+                file.save_with_encoding(encoding)
+            }
+        };
+        let file = self.context.file_manager_window.current_file();
+
+        self.context.status_message = match result {
+            Ok(()) => Some(format!(
+                "{} is now {}",
+                file.name.to_string_lossy(),
+                file.encoding
+            )),
+            Err(error) => Some(format!("Could not change the encoding: {}", error)),
+        };
+    }
+
+    // Opens the files named on the command line, in place of the placeholder files.
+    // A directory becomes the root of the explorer instead, and a path that
+    // does not exist opens an empty file that is created when it is saved.
+    // The path "-" reads stdin into an unnamed file.
+    pub fn open_targets(&mut self, targets: Vec<FileTarget>) {
+        let (dirs, files): (Vec<FileTarget>, Vec<FileTarget>) =
+            targets.into_iter().partition(|target| target.path.is_dir());
+
+        if let Some(dir) = dirs.last() {
+            let navigation_window = &mut self.context.navigation_window;
+            navigation_window.explorer.set_root(dir.path.clone());
+
+            if files.is_empty() {
+                navigation_window.focus = NavigationFocus::Explorer;
+                self.context.current_window = WindowState::Navigation;
+            }
+        }
+
+        self.context.unnamed_swaps = swap::orphaned_unnamed();
+
+        // The placeholder files stay only when nothing was given. A directory
+        // alone leaves an unnamed file next to the explorer.
+        if dirs.is_empty() && files.is_empty() {
+            self.check_swaps();
+            return;
+        }
+
+        let file_manager = &mut self.context.file_manager_window;
+        file_manager.file_list.clear();
+        file_manager.current_file_index = 0;
+
+        let mut errors = Vec::new();
+        for target in files {
+            let path = target.path.clone().into_boxed_path();
+            let opened = match path.exists() {
+                _ if target.path.as_os_str() == "-" => {
+                    FileWindow::from_reader("stdin", std::io::stdin().lock()).map(|mut file| {
+                        file.piped = file_manager.stdout;
+                        file_manager.add_file_menu(file);
+                        file_manager.current_file_index = file_manager.file_list.len() - 1;
+                    })
+                }
+                true => file_manager.open_file(path),
+                false => {
+                    file_manager.add_file_menu(FileWindow::new_file(path));
+                    file_manager.current_file_index = file_manager.file_list.len() - 1;
+                    Ok(())
+                }
+            };
+
+            match opened {
+                Ok(()) => {
+                    if let Some(line) = target.line {
+                        let column = target.column.unwrap_or(1);
+                        file_manager.current_file().go_to(line, column);
+                    }
+                }
+                Err(error) => errors.push(format!("{}: {}", target.path.display(), error)),
+            }
+        }
+
+        if file_manager.file_list.is_empty() {
+            file_manager.add_file_menu(FileWindow::new("untitled"));
+        }
+        file_manager.current_file_index = 0;
+
+        if !errors.is_empty() {
+            self.context.status_message = Some(format!("Could not open {}", errors.join(", ")));
+        }
+
+        self.check_swaps();
+    }
+
+    // Whether a dialog, prompt or the finder is open and takes all key presses.
+    // The current file is not changed from under them.
+    fn modal_open(&self) -> bool {
+        let context = &self.context;
+        context.recover_window.is_some()
+            || context.changed_window.is_some()
+            || context.confirm_window.is_some()
+            || context.prompt_window.is_some()
+            || context.finder_window.is_some()
+    }
+
+    // Looks for swap files left behind for the files that were opened since the
+    // last check, and asks about the first one found. The others are asked
+    // about in turn, as each answer calls this again. The swap files of unnamed
+    // files come last, each offered in a new unnamed file.
+    pub fn check_swaps(&mut self) {
+        if self.modal_open() {
+            return;
+        }
+
+        let file_manager = &mut self.context.file_manager_window;
+        for (file_index, file) in file_manager.file_list.iter_mut().enumerate() {
+            if file.swap_checked {
+                continue;
+            }
+            file.swap_checked = true;
+
+            let path = match &file.path {
+                Some(path) => path.clone(),
+                None => continue,
+            };
+            let swap = match swap::read(&path) {
+                Some(swap) => swap,
+                None => continue,
+            };
+
+            let owned_by_other = swap.is_owned_by_other();
+            // A swap file with the same text as the file holds nothing to recover.
+            if !owned_by_other && swap.text == file.buffer.to_string() {
+                swap::remove(&path);
+                continue;
+            }
+
+            let age = swap.modified.elapsed().map_or(0, |age| age.as_secs() / 60);
+            let mut message = format!(
+                "Found unsaved changes to {} from {} minutes ago.",
+                file.name.to_string_lossy(),
+                age
+            );
+            if owned_by_other {
+                message.push_str(&format!(
+                    " The file is open in another rustex (pid {}).",
+                    swap.pid
+                ));
+            } else if path
+                .metadata()
+                .and_then(|m| m.modified())
+                .is_ok_and(|modified| modified > swap.modified)
+            {
+                message.push_str(" The file was saved after them.");
+            }
+
+            file_manager.current_file_index = file_index;
+            self.context.current_window = WindowState::Editor;
+            self.context.recover_window = Some(RecoverWindow::new(message, file_index, swap));
+            return;
+        }
+
+        if let Some(swap) = self.context.unnamed_swaps.pop() {
+            let name = swap.unnamed_name().unwrap_or("untitled");
+            let mut file = FileWindow::new(name);
+            file.swap_checked = true;
+            file_manager.add_file_menu(file);
+
+            let age = swap.modified.elapsed().map_or(0, |age| age.as_secs() / 60);
+            let message = format!(
+                "Found unsaved changes to the unnamed file {} from {} minutes ago.",
+                name, age
+            );
+            let file_index = file_manager.file_list.len() - 1;
+            file_manager.current_file_index = file_index;
+            self.context.current_window = WindowState::Editor;
+            self.context.recover_window = Some(RecoverWindow::new(message, file_index, swap));
+        }
+    }
+
+    // Acts on the answer of the recover dialog, once the user has picked one.
+    fn handle_recover_choice(&mut self) {
+        let recover_window = match &mut self.context.recover_window {
+            Some(recover_window) => recover_window,
+            None => return,
+        };
+        let choice = match recover_window.choice.take() {
+            Some(choice) => choice,
+            None => return,
+        };
+
+        let file_index = recover_window.file_index;
+        let file = &mut self.context.file_manager_window.file_list[file_index];
+        // Both editors writing the same swap file would lose the changes of one.
+        let owned_by_other = recover_window.contents.is_owned_by_other();
+
+        match choice {
+            // The dialog stays open with the diff, so the user can still pick.
+            RecoverChoice::Diff => {
+                let mut lines = vec![
+                    format!("--- {}", file.name.to_string_lossy()),
+                    format!("+++ {} (unsaved changes)", file.name.to_string_lossy()),
+                ];
+                lines.extend(diff::unified(
+                    &file.buffer.to_string(),
+                    &recover_window.contents.text,
+                ));
+                recover_window.diff = Some(lines);
+                recover_window.scroll = 0;
+                return;
+            }
+            RecoverChoice::Recover => {
+                file.swap_foreign = owned_by_other;
+                file.recover(&recover_window.contents.text);
+                // The swap file now belongs to this editor, and goes away on save.
+                // An unnamed file keeps writing to the one it was recovered from.
+                if file.path.is_none() {
+                    file.swap_key = Some(recover_window.contents.key.clone());
+                }
+                file.write_swap();
+                self.context.status_message =
+                    Some(format!("Recovered {}", file.name.to_string_lossy()));
+            }
+            // The swap file of another running editor is still in use, so it stays.
+            RecoverChoice::Discard if owned_by_other => {
+                self.context.status_message = Some(format!(
+                    "Kept the swap file of the rustex with pid {}",
+                    recover_window.contents.pid
+                ));
+            }
+            RecoverChoice::Discard => swap::remove_key(&recover_window.contents.key),
+            RecoverChoice::Ignore => {}
+        }
+
+        file.swap_foreign = owned_by_other;
+
+        // The unnamed file opened for a swap file goes away again when nothing
+        // was recovered into it. An ignored swap file is offered on the next start.
+        if file.path.is_none() && !file.is_modified() {
+            self.context.file_manager_window.close_file(file_index);
+        }
+
+        self.context.recover_window = None;
+        self.check_swaps();
+    }
+
+    // Compares the open files with the files on disk, when the watcher saw a
+    // change or it is time to poll, or always when forced. Unmodified files are
+    // reloaded, except large ones, which are marked stale until a forced check.
+    // A modified file gets the changed dialog, as does a stale one when forced,
+    // and a file that is gone is followed to where it was moved, or marked as deleted.
+    // Nothing is checked while another modal is open, as the dialog makes the
+    // file it is about current. Returns whether the changed dialog is open.
+    pub fn check_disk_changes(&mut self, force: bool) -> bool {
+        if self.modal_open() {
+            return self.context.changed_window.is_some();
+        }
+
+        let file_manager = &mut self.context.file_manager_window;
+        let watcher = &mut self.context.file_watcher;
+        watcher.watch(
+            file_manager
+                .file_list
+                .iter()
+                .filter_map(|file| file.path.as_deref()),
+        );
+        if !watcher.should_check() && !force {
+            return false;
+        }
+
+        let mut messages = Vec::new();
+        for (file_index, file) in file_manager.file_list.iter_mut().enumerate() {
+            let path = match &file.path {
+                Some(path) => path.clone(),
+                None => continue,
+            };
+            let disk_state = DiskState::read(&path);
+            if disk_state == file.disk_state {
+                continue;
+            }
+            let name = file.name.to_string_lossy().to_string();
+
+            if disk_state.is_none() {
+                file.disk_state = None;
+                match watcher.take_move(&path) {
+                    // The swap file is named after the path, so it moves along.
+                    Some(to) => {
+                        file.remove_swap();
+                        file.disk_state = DiskState::read(&to);
+                        file.name = to.file_name().unwrap_or(to.as_os_str()).to_os_string();
+                        messages.push(format!("{} was moved to {}", name, to.display()));
+                        file.path = Some(to.into_boxed_path());
+                    }
+                    None => {
+                        file.deleted = true;
+                        messages.push(format!("{} was deleted from disk", name));
+                    }
+                }
+                continue;
+            }
+
+            // A large unmodified file is only marked stale until it is saved,
+            // which asks about the change first.
+            if !force
+                && !file.has_changes()
+                && disk_state.is_some_and(|state| state.size() > watch::RELOAD_LIMIT)
+            {
+                if !file.stale {
+                    file.stale = true;
+                    messages.push(format!(
+                        "{} changed on disk, it is too large to reload",
+                        name
+                    ));
+                }
+                continue;
+            }
+
+            // A file that cannot be read right now is tried again on the next check.
+            let bytes = match std::fs::read(&path) {
+                Ok(bytes) => bytes,
+                Err(_) => continue,
+            };
+            if !file.has_changes() && !file.stale {
+                file.reload(bytes, disk_state);
+                messages.push(format!("Reloaded {}, it changed on disk", name));
+                continue;
+            }
+            // Only a modified file is compared, to not ask about the
+            // file being saved with the same changes elsewhere.
+            if file.encoded().is_ok_and(|contents| contents == bytes) {
+                file.reload(bytes, disk_state);
+                continue;
+            }
+
+            let message = match file.has_changes() {
+                true => format!("{} changed on disk, and has unsaved changes here.", name),
+                false => format!("{} changed on disk, and was too large to reload.", name),
+            };
+            file_manager.current_file_index = file_index;
+            self.context.current_window = WindowState::Editor;
+            self.context.changed_window = Some(ChangedWindow::new(
+                message,
+                file_index,
+                DiskContents { bytes, disk_state },
+            ));
+            break;
+        }
+        watcher.clear_moves();
+
+        if !messages.is_empty() {
+            self.context.status_message = Some(messages.join(". "));
+        }
+        self.context.changed_window.is_some()
+    }
+
+    // Acts on the answer of the changed dialog, once the user has picked one.
+    fn handle_changed_choice(&mut self) {
+        let changed_window = match &mut self.context.changed_window {
+            Some(changed_window) => changed_window,
+            None => return,
+        };
+        let choice = match changed_window.choice.take() {
+            Some(choice) => choice,
+            None => return,
+        };
+
+        let file = &mut self.context.file_manager_window.file_list[changed_window.file_index];
+        let name = file.name.to_string_lossy().to_string();
+
+        match choice {
+            // The dialog stays open with the diff, so the user can still pick.
+            ChangedChoice::Diff => {
+                let bytes = &changed_window.contents.bytes;
+                let (ours, theirs) = match &file.hex {
+                    Some(hex) => (hex::dump(&hex.bytes), hex::dump(bytes)),
+                    None => (
+                        file.buffer.to_string(),
+                        encoding::decode(bytes, file.encoding)
+                            .unwrap_or_else(|| encoding::decode_detected(bytes))
+                            .0,
+                    ),
+                };
+                let mut lines = vec![
+                    format!("--- {} (unsaved changes)", name),
+                    format!("+++ {} (on disk)", name),
+                ];
+                lines.extend(diff::unified(&ours, &theirs));
+                changed_window.diff = Some(lines);
+                changed_window.scroll = 0;
+                return;
+            }
+            ChangedChoice::Reload => {
+                let contents = &mut changed_window.contents;
+                file.reload(std::mem::take(&mut contents.bytes), contents.disk_state);
+                self.context.status_message =
+                    Some(format!("Reloaded {}, the changes here are discarded", name));
+            }
+            // The file on disk is now the one the changes are saved over.
+            ChangedChoice::Keep => {
+                file.disk_state = changed_window.contents.disk_state;
+                file.deleted = false;
+                file.stale = false;
+                self.context.status_message = Some(format!(
+                    "Kept the changes to {}, saving overwrites the file on disk",
+                    name
+                ));
+            }
+        }
+
+        self.context.changed_window = None;
+        self.check_disk_changes(true);
+    }
+
+    // The finder searches the directory the editor was started in.
+    pub fn open_finder(&mut self) {
+        match std::env::current_dir() {
+            Ok(root) => self.context.finder_window = Some(FinderWindow::new(root)),
+            Err(error) => {
+                self.context.status_message = Some(format!("Could not search files: {}", error))
+            }
+        }
+    }
+
+    fn handle_finder_result(&mut self) {
+        let result = match &mut self.context.finder_window {
+            Some(finder) => finder.result.take(),
+            None => return,
+        };
+
+        match result {
+            Some(FinderResult::Open(path)) => {
+                self.context.finder_window = None;
+                let file_manager = &mut self.context.file_manager_window;
+                if let Err(error) = file_manager.open_file(path.into()) {
+                    self.context.status_message = Some(format!("Could not open: {}", error));
+                }
+            }
+            Some(FinderResult::Cancelled) => self.context.finder_window = None,
+            None => (),
+        }
+    }
+
+    // Bracketed paste delivers the whole pasted text at once,
+    // so it is inserted as a single edit instead of one key press per char.
+    // Like key presses, it goes to the prompt or finder when one is open,
+    // and dialogs, which only take keys that pick an answer, drop it.
+    pub fn handle_paste(&mut self, text: &str) {
+        // Terminals send pasted line breaks as "\r".
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+
+        let context = &mut self.context;
+        if context.recover_window.is_some()
+            || context.changed_window.is_some()
+            || context.confirm_window.is_some()
+        {
+            return;
+        }
+        // Inputs are a single line, so line breaks become spaces.
+        if let Some(prompt_window) = &mut context.prompt_window {
+            text.chars()
+                .map(|c| if c == '\n' { ' ' } else { c })
+                .for_each(|c| prompt_window.insert_char(c));
+            return;
+        }
+        if let Some(finder_window) = &mut context.finder_window {
+            finder_window
+                .query
+                .extend(text.chars().filter(|c| *c != '\n'));
+            finder_window.update_matches();
+            return;
+        }
+
+        match self.context.current_window {
+            WindowState::Editor => {
+                self.context
+                    .file_manager_window
+                    .current_file()
+                    .paste_text(&text);
+            }
+            WindowState::Navigation | WindowState::FileMenu => {}
+        }
+    }
+
     // handle_keyboard_event is the main function for sorting
     // out what happens when a key is pressed.
     pub fn handle_keyboard_event(&mut self, event: crossterm::event::KeyEvent) {
@@ -80,7 +1225,49 @@ impl App {
 
         // println!("Keycode: {:?} Keyflags: {:b}", keycode, keyflags);
 
+        self.context.status_message = None;
+
+        if let Some(recover_window) = &mut self.context.recover_window {
+            recover_window.handle_keyboard_input(keycode, keyflags);
+            self.handle_recover_choice();
+            return;
+        }
+
+        if let Some(changed_window) = &mut self.context.changed_window {
+            changed_window.handle_keyboard_input(keycode, keyflags);
+            self.handle_changed_choice();
+            return;
+        }
+
+        if let Some(confirm_window) = &mut self.context.confirm_window {
+            confirm_window.handle_keyboard_input(keycode, keyflags);
+            self.handle_confirm_choice();
+            return;
+        }
+
+        if let Some(prompt_window) = &mut self.context.prompt_window {
+            prompt_window.handle_keyboard_input(keycode, keyflags);
+            self.handle_prompt_result();
+            return;
+        }
+
+        if let Some(finder_window) = &mut self.context.finder_window {
+            finder_window.handle_keyboard_input(keycode, keyflags);
+            self.handle_finder_result();
+            return;
+        }
+
         match (keycode, keyflags) {
+            // Handle closing the application with the escape key
+            (KeyCode::Esc, _) => self.request_quit(0),
+            (KeyCode::Char('w'), 0b0010) => self.request_close(),
+            (KeyCode::Char('s'), 0b0010) => self.save_current_file(),
+            (KeyCode::Char('o'), 0b0010) => self.open_finder(),
+            (KeyCode::Char('f'), 0b0010) => self.open_prompt(PromptKind::Find, "Find", ""),
+            (KeyCode::Char('g'), 0b0010) => {
+                self.open_prompt(PromptKind::GoToLine, "Go to line", "")
+            }
+
             (KeyCode::Char('b'), 0b0010) => {
                 if self.context.current_window != WindowState::Navigation {
                     self.context.current_window = WindowState::Navigation;
@@ -100,11 +1287,21 @@ impl App {
                     self.context
                         .navigation_window
                         .handle_keyboard_input(keycode, keyflags);
+
+                    let navigation_window = &mut self.context.navigation_window;
+                    if let Some(action) = navigation_window.pending_action.take() {
+                        self.run_action(action);
+                    } else if let Some(action) = navigation_window.explorer.pending_action.take() {
+                        self.run_explorer_action(action);
+                    }
                 }
                 WindowState::FileMenu => {}
             },
         }
 
+        // Any key may have opened files.
+        self.check_swaps();
+
         // dbg!(flags);
 
         // if !file_edit_mode {
@@ -148,3 +1345,134 @@ impl App {
         // }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn target(path: &str, line: Option<usize>, column: Option<usize>) -> FileTarget {
+        FileTarget {
+            path: PathBuf::from(path),
+            line,
+            column,
+        }
+    }
+
+    fn parse_args(args: &[&str]) -> Vec<FileTarget> {
+        let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+        FileTarget::parse_args(&args)
+    }
+
+    #[test]
+    fn parses_lines_and_columns() {
+        assert_eq!(
+            FileTarget::parse("notes.txt"),
+            target("notes.txt", None, None)
+        );
+        assert_eq!(
+            FileTarget::parse("notes.txt:12"),
+            target("notes.txt", Some(12), None)
+        );
+        assert_eq!(
+            FileTarget::parse("notes.txt:12:5"),
+            target("notes.txt", Some(12), Some(5))
+        );
+        assert_eq!(
+            FileTarget::parse("dir/a:b.txt:3"),
+            target("dir/a:b.txt", Some(3), None)
+        );
+    }
+
+    #[test]
+    fn keeps_names_that_are_not_positions() {
+        assert_eq!(
+            FileTarget::parse("notes.txt:x"),
+            target("notes.txt:x", None, None)
+        );
+        assert_eq!(FileTarget::parse(":12"), target(":12", None, None));
+        assert_eq!(FileTarget::parse("notes:"), target("notes:", None, None));
+    }
+
+    #[test]
+    fn opens_existing_names_with_colons_as_they_are() {
+        let path = std::env::temp_dir().join(format!("rustex-target-{}:3", std::process::id()));
+        std::fs::write(&path, "").unwrap();
+        let parsed = FileTarget::parse(path.to_str().unwrap());
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(parsed.path, path);
+        assert_eq!(parsed.line, None);
+    }
+
+    #[test]
+    fn plus_line_applies_to_the_next_file() {
+        assert_eq!(
+            parse_args(&["+7", "a.txt", "b.txt", "+2", "c.txt:9"]),
+            [
+                target("a.txt", Some(7), None),
+                target("b.txt", None, None),
+                target("c.txt", Some(9), None),
+            ]
+        );
+    }
+
+    #[test]
+    fn stdout_pipes_the_file_current_when_quitting() {
+        let mut app = App::new("Rustex");
+        let file_manager = &mut app.context.file_manager_window;
+        file_manager.stdout = true;
+        file_manager.file_list[0].handle_keyboard_input(KeyCode::Char('a'), 0);
+        file_manager.current_file_index = 1;
+
+        // Asking about the modified first file makes it current,
+        // but the second one stays piped.
+        app.request_quit(0);
+        assert!(app.context.confirm_window.is_some());
+        let file_manager = &app.context.file_manager_window;
+        assert_eq!(file_manager.current_file_index, 0);
+        assert!(!file_manager.file_list[0].piped);
+        assert!(file_manager.file_list[1].piped);
+    }
+
+    #[test]
+    fn stdout_keeps_the_stdin_buffer_piped() {
+        let mut app = App::new("Rustex");
+        let file_manager = &mut app.context.file_manager_window;
+        file_manager.stdout = true;
+        file_manager.file_list[1].piped = true;
+
+        app.request_quit(0);
+        assert!(app.should_quit);
+        let file_manager = &app.context.file_manager_window;
+        assert!(!file_manager.file_list[0].piped);
+        assert!(file_manager.file_list[1].piped);
+    }
+
+    #[test]
+    fn large_changed_files_are_marked_stale() {
+        let path = std::env::temp_dir().join(format!("rustex-stale-{}.log", std::process::id()));
+        let contents = vec![b'x'; watch::RELOAD_LIMIT as usize];
+        std::fs::write(&path, &contents).unwrap();
+
+        let mut app = App::new("Rustex");
+        let file_manager = &mut app.context.file_manager_window;
+        let file = FileWindow::open_file(path.clone().into_boxed_path()).unwrap();
+        file_manager.file_list.clear();
+        file_manager.add_file_menu(file);
+
+        let mut appended = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        std::io::Write::write_all(&mut appended, b"more\n").unwrap();
+
+        assert!(!app.check_disk_changes(false));
+        let file = &app.context.file_manager_window.file_list[0];
+        assert!(file.stale);
+        assert_eq!(file.buffer.len_bytes(), contents.len());
+
+        // Saving checks with force, and asks before overwriting the change.
+        assert!(app.check_disk_changes(true));
+        std::fs::remove_file(&path).unwrap();
+    }
+}
@@ -0,0 +1,319 @@
+use std::io::Read;
+use std::ops::Range;
+
+use ropey::{Rope, RopeSlice};
+use unicode_segmentation::UnicodeSegmentation;
+
+/// The line ending style of a file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineEnding {
+    #[default]
+    Lf,
+    CrLf,
+    Cr,
+    // The file uses more than one style.
+    Mixed,
+}
+
+impl LineEnding {
+    // The line break of the style. Mixed files get "\n".
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+            LineEnding::Lf | LineEnding::Mixed => "\n",
+        }
+    }
+}
+
+// Parses the styles a file can be converted to, so not "mixed".
+impl std::str::FromStr for LineEnding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<LineEnding, String> {
+        match s.to_lowercase().as_str() {
+            "lf" | "unix" => Ok(LineEnding::Lf),
+            "crlf" | "dos" | "windows" => Ok(LineEnding::CrLf),
+            "cr" | "mac" => Ok(LineEnding::Cr),
+            _ => Err(format!("Not a line ending: {}", s)),
+        }
+    }
+}
+
+impl std::fmt::Display for LineEnding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LineEnding::Lf => "LF",
+            LineEnding::CrLf => "CRLF",
+            LineEnding::Cr => "CR",
+            LineEnding::Mixed => "Mixed",
+        })
+    }
+}
+
+/// TextBuffer is the storage behind every open file.
+/// It is backed by a rope, so inserts, deletes and line lookups are
+/// O(log n) regardless of how large the file is.
+///
+/// All positions are char indices unless the name says otherwise.
+#[derive(Clone, Default, Debug)]
+pub struct TextBuffer {
+    rope: Rope,
+}
+
+impl TextBuffer {
+    pub fn new() -> TextBuffer {
+        TextBuffer { rope: Rope::new() }
+    }
+
+    pub fn from_reader<R: Read>(reader: R) -> Result<TextBuffer, std::io::Error> {
+        Ok(TextBuffer {
+            rope: Rope::from_reader(reader)?,
+        })
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.rope.len_chars()
+    }
+
+    pub fn len_bytes(&self) -> usize {
+        self.rope.len_bytes()
+    }
+
+    // A buffer always has at least one line, even when it is empty.
+    pub fn len_lines(&self) -> usize {
+        self.rope.len_lines()
+    }
+
+    pub fn insert(&mut self, char_idx: usize, text: &str) {
+        self.rope.insert(char_idx, text);
+    }
+
+    pub fn remove(&mut self, char_range: Range<usize>) {
+        self.rope.remove(char_range);
+    }
+
+    pub fn char(&self, char_idx: usize) -> char {
+        self.rope.char(char_idx)
+    }
+
+    pub fn slice(&self, char_range: Range<usize>) -> RopeSlice<'_> {
+        self.rope.slice(char_range)
+    }
+
+    /// Returns the line including its line ending.
+    pub fn line(&self, line_idx: usize) -> RopeSlice<'_> {
+        self.rope.line(line_idx)
+    }
+
+    /// Returns the line without its line ending.
+    pub fn line_text(&self, line_idx: usize) -> RopeSlice<'_> {
+        let line = self.rope.line(line_idx);
+        line.slice(..line.len_chars() - line_ending_len(line))
+    }
+
+    /// The number of chars in a line, not counting the line ending.
+    pub fn line_len(&self, line_idx: usize) -> usize {
+        let line = self.rope.line(line_idx);
+        line.len_chars() - line_ending_len(line)
+    }
+
+    /// The number of chars used by the line ending of a line.
+    /// This is 0 for the last line, 2 for "\r\n" and 1 otherwise.
+    pub fn line_ending_len(&self, line_idx: usize) -> usize {
+        line_ending_len(self.rope.line(line_idx))
+    }
+
+    /// Splits a line into its extended grapheme clusters, without the line ending.
+    pub fn line_graphemes(&self, line_idx: usize) -> Vec<String> {
+        self.line_text(line_idx)
+            .to_string()
+            .graphemes(true)
+            .map(|g| g.to_string())
+            .collect()
+    }
+
+    /// The number of graphemes in a line, not counting the line ending.
+    pub fn line_grapheme_len(&self, line_idx: usize) -> usize {
+        self.line_text(line_idx).to_string().graphemes(true).count()
+    }
+
+    /// Converts a grapheme column in a line to a char column in the same line.
+    /// Columns past the end of the line are clamped to the end.
+    pub fn grapheme_to_char(&self, line_idx: usize, grapheme_x: usize) -> usize {
+        self.line_text(line_idx)
+            .to_string()
+            .graphemes(true)
+            .take(grapheme_x)
+            .map(|g| g.chars().count())
+            .sum()
+    }
+
+    /// Converts a char column in a line to a grapheme column.
+    /// A column inside a grapheme is rounded up to the end of that grapheme.
+    pub fn char_to_grapheme(&self, line_idx: usize, char_x: usize) -> usize {
+        let mut chars = 0;
+        let mut graphemes = 0;
+        for g in self.line_text(line_idx).to_string().graphemes(true) {
+            if chars >= char_x {
+                break;
+            }
+            chars += g.chars().count();
+            graphemes += 1;
+        }
+        graphemes
+    }
+
+    /// Looks at every line ending to find the style the buffer uses.
+    /// A buffer without any line breaks is reported as LF.
+    pub fn detect_line_ending(&self) -> LineEnding {
+        let mut found = None;
+
+        for line in self.rope.lines() {
+            let ending = match slice_line_ending(line) {
+                Some(ending) => ending,
+                None => continue,
+            };
+
+            match found {
+                None => found = Some(ending),
+                Some(style) if style != ending => return LineEnding::Mixed,
+                _ => {}
+            }
+        }
+
+        found.unwrap_or_default()
+    }
+
+    /// The line ending style of a line, None for the last line.
+    pub fn line_ending(&self, line_idx: usize) -> Option<LineEnding> {
+        slice_line_ending(self.rope.line(line_idx))
+    }
+
+    /// Whether the text ends with a line break, as text files usually do.
+    /// An empty buffer counts as ending with one, as there is nothing to end.
+    pub fn ends_with_line_break(&self) -> bool {
+        let len = self.len_chars();
+        len == 0 || matches!(self.rope.char(len - 1), '\n' | '\r')
+    }
+
+    /// Finds the next occurrence of `pattern` at or after `from_char`,
+    /// wrapping around to the start of the buffer. Returns its char index.
+    pub fn find(&self, pattern: &str, from_char: usize) -> Option<usize> {
+        if pattern.is_empty() {
+            return None;
+        }
+
+        let text = self.rope.to_string();
+        let from_byte = self.rope.char_to_byte(from_char.min(self.len_chars()));
+        let byte_idx = text[from_byte..]
+            .find(pattern)
+            .map(|i| from_byte + i)
+            .or_else(|| text.find(pattern))?;
+
+        Some(self.rope.byte_to_char(byte_idx))
+    }
+
+    pub fn line_to_char(&self, line_idx: usize) -> usize {
+        self.rope.line_to_char(line_idx)
+    }
+
+    pub fn char_to_line(&self, char_idx: usize) -> usize {
+        self.rope.char_to_line(char_idx)
+    }
+
+    pub fn char_to_byte(&self, char_idx: usize) -> usize {
+        self.rope.char_to_byte(char_idx)
+    }
+
+    pub fn byte_to_char(&self, byte_idx: usize) -> usize {
+        self.rope.byte_to_char(byte_idx)
+    }
+
+    pub fn line_to_byte(&self, line_idx: usize) -> usize {
+        self.rope.line_to_byte(line_idx)
+    }
+
+    pub fn byte_to_line(&self, byte_idx: usize) -> usize {
+        self.rope.byte_to_line(byte_idx)
+    }
+
+    pub fn lines(&self) -> ropey::iter::Lines<'_> {
+        self.rope.lines()
+    }
+
+    pub fn chunks(&self) -> ropey::iter::Chunks<'_> {
+        self.rope.chunks()
+    }
+
+    pub fn write_to<W: std::io::Write>(&self, writer: W) -> Result<(), std::io::Error> {
+        self.rope.write_to(writer)
+    }
+}
+
+impl From<&str> for TextBuffer {
+    fn from(text: &str) -> TextBuffer {
+        TextBuffer {
+            rope: Rope::from_str(text),
+        }
+    }
+}
+
+impl std::fmt::Display for TextBuffer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for chunk in self.rope.chunks() {
+            f.write_str(chunk)?;
+        }
+        Ok(())
+    }
+}
+
+fn slice_line_ending(line: RopeSlice) -> Option<LineEnding> {
+    match line_ending_len(line) {
+        0 => None,
+        2 => Some(LineEnding::CrLf),
+        _ if line.char(line.len_chars() - 1) == '\r' => Some(LineEnding::Cr),
+        _ => Some(LineEnding::Lf),
+    }
+}
+
+fn line_ending_len(line: RopeSlice) -> usize {
+    let len = line.len_chars();
+    if len == 0 {
+        return 0;
+    }
+
+    match line.char(len - 1) {
+        '\n' if len > 1 && line.char(len - 2) == '\r' => 2,
+        '\n' | '\r' => 1,
+        _ => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detects_line_endings() {
+        let detect = |text: &str| TextBuffer::from(text).detect_line_ending();
+
+        assert_eq!(detect("a\nb\n"), LineEnding::Lf);
+        assert_eq!(detect("a\r\nb\r\n"), LineEnding::CrLf);
+        assert_eq!(detect("a\rb\r"), LineEnding::Cr);
+        assert_eq!(detect("a\r\nb\n"), LineEnding::Mixed);
+        // Without any line break the default is used.
+        assert_eq!(detect("a"), LineEnding::Lf);
+        assert_eq!(detect(""), LineEnding::Lf);
+    }
+
+    #[test]
+    fn parses_line_endings() {
+        assert_eq!("LF".parse(), Ok(LineEnding::Lf));
+        assert_eq!("unix".parse(), Ok(LineEnding::Lf));
+        assert_eq!("crlf".parse(), Ok(LineEnding::CrLf));
+        assert_eq!("Windows".parse(), Ok(LineEnding::CrLf));
+        assert_eq!("cr".parse(), Ok(LineEnding::Cr));
+        assert!("mixed".parse::<LineEnding>().is_err());
+    }
+}
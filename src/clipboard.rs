@@ -0,0 +1,311 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+use strum_macros::{Display, EnumString};
+
+use crate::terminal_guard;
+
+// The number of yanks kept in the yank history ring.
+const RING_SIZE: usize = 32;
+
+// The register used by copy, cut and paste when no register is given.
+pub const UNNAMED_REGISTER: char = '"';
+
+/// How copied text is shared with the system clipboard.
+#[derive(Clone, Copy, Debug, Default, Display, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum ClipboardBridge {
+    /// Copied text stays inside rustex.
+    #[default]
+    None,
+    /// OSC 52 escape sequences ask the terminal to set the clipboard.
+    /// This also works over SSH, but the clipboard can not be read back.
+    Osc52,
+    /// `wl-copy`/`wl-paste` or `xclip` are used to read and write the clipboard.
+    Command,
+    /// Use the external commands if one is installed, otherwise OSC 52.
+    Auto,
+}
+
+// An external program pair used to write and read the system clipboard.
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct ClipboardCommand {
+    copy: Vec<&'static str>,
+    paste: Vec<&'static str>,
+}
+
+impl ClipboardCommand {
+    // Wayland is preferred when both a Wayland and an X display are available.
+    fn detect() -> Option<ClipboardCommand> {
+        let wayland = std::env::var_os("WAYLAND_DISPLAY").is_some();
+        let x11 = std::env::var_os("DISPLAY").is_some();
+
+        if wayland && find_in_path("wl-copy") && find_in_path("wl-paste") {
+            Some(ClipboardCommand {
+                copy: vec!["wl-copy"],
+                paste: vec!["wl-paste", "--no-newline"],
+            })
+        } else if x11 && find_in_path("xclip") {
+            Some(ClipboardCommand {
+                copy: vec!["xclip", "-selection", "clipboard", "-in"],
+                paste: vec!["xclip", "-selection", "clipboard", "-out"],
+            })
+        } else {
+            None
+        }
+    }
+
+    fn copy(&self, text: &str) -> Result<(), std::io::Error> {
+        let mut child = Command::new(self.copy[0])
+            .args(&self.copy[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes())?;
+        }
+
+        child.wait()?;
+        Ok(())
+    }
+
+    fn paste(&self) -> Option<String> {
+        let output = Command::new(self.paste[0])
+            .args(&self.paste[1..])
+            .stdin(Stdio::null())
+            .stderr(Stdio::null())
+            .output()
+            .ok()?;
+
+        if output.status.success() {
+            String::from_utf8(output.stdout).ok()
+        } else {
+            None
+        }
+    }
+}
+
+fn find_in_path(program: &str) -> bool {
+    std::env::var_os("PATH")
+        .map(|paths| std::env::split_paths(&paths).any(|dir| dir.join(program).is_file()))
+        .unwrap_or(false)
+}
+
+/// Clipboard holds the registers shared by all open files.
+///
+/// A copy goes to the unnamed register and to the front of the yank ring,
+/// unless a named register was selected for it. The numbered registers
+/// '0' to '9' read from the yank ring, '0' being the latest.
+#[derive(Clone, Default, Debug)]
+pub struct Clipboard {
+    registers: HashMap<char, String>,
+    ring: VecDeque<String>,
+    // The ring entry the next `paste_older` returns.
+    ring_index: usize,
+    // The register the next copy, cut or paste uses instead of the unnamed one.
+    selected: Option<char>,
+
+    bridge: ClipboardBridge,
+    command: Option<ClipboardCommand>,
+}
+
+impl Clipboard {
+    pub fn new(bridge: ClipboardBridge) -> Clipboard {
+        let command = match bridge {
+            ClipboardBridge::Command | ClipboardBridge::Auto => ClipboardCommand::detect(),
+            _ => None,
+        };
+
+        // Without an external command, auto falls back to OSC 52.
+        let bridge = match (bridge, &command) {
+            (ClipboardBridge::Auto, Some(_)) => ClipboardBridge::Command,
+            (ClipboardBridge::Auto, None) => ClipboardBridge::Osc52,
+            (bridge, _) => bridge,
+        };
+
+        Clipboard {
+            bridge,
+            command,
+            ..Default::default()
+        }
+    }
+
+    /// Picks the register of the next copy, cut or paste.
+    pub fn select_register(&mut self, name: char) {
+        self.selected = Some(name);
+    }
+
+    /// Stores copied text in the selected register, or else in the unnamed
+    /// register and the yank ring, and hands it to the system clipboard.
+    pub fn copy(&mut self, text: String) {
+        let name = self.selected.take().unwrap_or(UNNAMED_REGISTER);
+        self.set_register(name, text);
+    }
+
+    /// Returns the text for a paste, from the selected register if there is one.
+    /// Otherwise the system clipboard is used when it can be read, so text
+    /// copied in other programs can be pasted too.
+    pub fn paste(&mut self) -> Option<String> {
+        self.ring_index = 1;
+
+        if let Some(name) = self.selected.take() {
+            return self.register(name);
+        }
+
+        if let Some(text) = self.command.as_ref().and_then(|command| command.paste()) {
+            if !text.is_empty() {
+                return Some(text);
+            }
+        }
+
+        self.register(UNNAMED_REGISTER)
+    }
+
+    /// Walks back through the yank ring, one entry further on every call.
+    pub fn paste_older(&mut self) -> Option<String> {
+        if self.ring.is_empty() {
+            return None;
+        }
+
+        let text = self.ring[self.ring_index % self.ring.len()].clone();
+        self.ring_index = (self.ring_index + 1) % self.ring.len();
+        Some(text)
+    }
+
+    pub fn register(&self, name: char) -> Option<String> {
+        match name.to_digit(10) {
+            Some(index) => self.ring.get(index as usize).cloned(),
+            None => self.registers.get(&name).cloned(),
+        }
+    }
+
+    // The numbered registers are the yank ring, so a copy to one of them
+    // is an ordinary copy.
+    pub fn set_register(&mut self, name: char, text: String) {
+        if name == UNNAMED_REGISTER || name.is_ascii_digit() {
+            self.push_ring(text.clone());
+            self.send_to_system(&text);
+            self.registers.insert(UNNAMED_REGISTER, text);
+        } else {
+            self.registers.insert(name, text);
+        }
+    }
+
+    fn push_ring(&mut self, text: String) {
+        self.ring.push_front(text);
+        self.ring.truncate(RING_SIZE);
+        self.ring_index = 0;
+    }
+
+    // Failing to reach the system clipboard is not an error,
+    // the text is still in the registers.
+    fn send_to_system(&self, text: &str) {
+        match self.bridge {
+            // The sequence goes to the terminal the editor draws on,
+            // which is not stdout when the edited text is piped out.
+            ClipboardBridge::Osc52 => {
+                if let Ok(mut terminal) = terminal_guard::output() {
+                    let _ = terminal
+                        .write_all(&osc52_sequence(text))
+                        .and_then(|_| terminal.flush());
+                }
+            }
+            ClipboardBridge::Command => {
+                if let Some(command) = &self.command {
+                    let _ = command.copy(text);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// The escape sequence that asks the terminal to put `text` on the clipboard.
+pub fn osc52_sequence(text: &str) -> Vec<u8> {
+    format!("\x1b]52;c;{}\x07", base64_encode(text.as_bytes())).into_bytes()
+}
+
+fn base64_encode(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let b = [
+            chunk[0],
+            *chunk.get(1).unwrap_or(&0),
+            *chunk.get(2).unwrap_or(&0),
+        ];
+        let n = (b[0] as u32) << 16 | (b[1] as u32) << 8 | b[2] as u32;
+
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_registers_keep_their_own_text() {
+        let mut clipboard = Clipboard::default();
+        clipboard.copy("first".to_string());
+        clipboard.select_register('a');
+        clipboard.copy("named".to_string());
+        clipboard.copy("second".to_string());
+
+        assert_eq!(clipboard.paste().as_deref(), Some("second"));
+        clipboard.select_register('a');
+        assert_eq!(clipboard.paste().as_deref(), Some("named"));
+        // The named copy is not in the yank ring.
+        clipboard.select_register('1');
+        assert_eq!(clipboard.paste().as_deref(), Some("first"));
+        clipboard.select_register('b');
+        assert_eq!(clipboard.paste(), None);
+    }
+
+    #[test]
+    fn paste_older_walks_the_yank_ring() {
+        let mut clipboard = Clipboard::default();
+        for text in ["one", "two", "three"] {
+            clipboard.copy(text.to_string());
+        }
+
+        assert_eq!(clipboard.paste().as_deref(), Some("three"));
+        assert_eq!(clipboard.paste_older().as_deref(), Some("two"));
+        assert_eq!(clipboard.paste_older().as_deref(), Some("one"));
+        assert_eq!(clipboard.paste_older().as_deref(), Some("three"));
+    }
+
+    #[test]
+    fn base64_pads_partial_chunks() {
+        let cases = [
+            ("", ""),
+            ("f", "Zg=="),
+            ("fo", "Zm8="),
+            ("foo", "Zm9v"),
+            ("foob", "Zm9vYg=="),
+            ("fooba", "Zm9vYmE="),
+            ("foobar", "Zm9vYmFy"),
+        ];
+        for (input, expected) in cases {
+            assert_eq!(base64_encode(input.as_bytes()), expected, "{:?}", input);
+        }
+    }
+
+    #[test]
+    fn osc52_sequence_sets_the_clipboard_selection() {
+        assert_eq!(osc52_sequence("héllo"), b"\x1b]52;c;aMOpbGxv\x07");
+        assert_eq!(osc52_sequence(""), b"\x1b]52;c;\x07");
+    }
+}
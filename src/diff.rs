@@ -0,0 +1,188 @@
+// Line based diffs, shown before the text of a file is replaced by other text.
+
+// The number of unchanged lines shown around each change.
+const CONTEXT: usize = 3;
+// Beyond this many cells the table of common lines gets too large,
+// and the changed lines are shown as one removed and one added block.
+const MAX_TABLE: usize = 4_000_000;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Op {
+    Same,
+    Removed,
+    Added,
+}
+
+/// Compares two texts line by line and returns a unified diff without file names:
+/// "@@ -1,4 +1,5 @@" headers, each followed by lines starting with ' ', '-' or '+'.
+/// Equal texts give no lines.
+pub fn unified(old: &str, new: &str) -> Vec<String> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    // Every op with the index of its line in the old and the new text.
+    let mut ops = Vec::new();
+    let (mut old_index, mut new_index) = (0, 0);
+    for op in diff_lines(&old, &new) {
+        ops.push((op, old_index, new_index));
+        match op {
+            Op::Same => {
+                old_index += 1;
+                new_index += 1;
+            }
+            Op::Removed => old_index += 1,
+            Op::Added => new_index += 1,
+        }
+    }
+
+    let mut lines = Vec::new();
+    let mut start = 0;
+    while let Some(first) = (start..ops.len()).find(|i| ops[*i].0 != Op::Same) {
+        // Changes that are close enough to share their context go in the same hunk.
+        let mut end = first;
+        loop {
+            while end < ops.len() && ops[end].0 != Op::Same {
+                end += 1;
+            }
+            match (end..ops.len()).find(|i| ops[*i].0 != Op::Same) {
+                Some(next) if next - end <= 2 * CONTEXT => end = next,
+                _ => break,
+            }
+        }
+
+        let from = first.saturating_sub(CONTEXT).max(start);
+        let to = (end + CONTEXT).min(ops.len());
+        let hunk = &ops[from..to];
+
+        let old_count = hunk.iter().filter(|op| op.0 != Op::Added).count();
+        let new_count = hunk.iter().filter(|op| op.0 != Op::Removed).count();
+        // An empty range is numbered after the line it follows.
+        let old_start = hunk[0].1 + (old_count > 0) as usize;
+        let new_start = hunk[0].2 + (new_count > 0) as usize;
+        lines.push(format!(
+            "@@ -{},{} +{},{} @@",
+            old_start, old_count, new_start, new_count
+        ));
+
+        for (op, old_index, new_index) in hunk {
+            lines.push(match op {
+                Op::Same => format!(" {}", old[*old_index]),
+                Op::Removed => format!("-{}", old[*old_index]),
+                Op::Added => format!("+{}", new[*new_index]),
+            });
+        }
+
+        start = to;
+    }
+
+    lines
+}
+
+// The ops that turn old into new, keeping the longest common subsequence of lines.
+fn diff_lines(old: &[&str], new: &[&str]) -> Vec<Op> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut ops = vec![Op::Same; prefix];
+
+    if a.len().saturating_mul(b.len()) > MAX_TABLE {
+        ops.extend(std::iter::repeat_n(Op::Removed, a.len()));
+        ops.extend(std::iter::repeat_n(Op::Added, b.len()));
+    } else {
+        // lengths[i * width + j] is the length of the common subsequence of a[i..] and b[j..].
+        let width = b.len() + 1;
+        let mut lengths = vec![0u32; (a.len() + 1) * width];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                lengths[i * width + j] = if a[i] == b[j] {
+                    lengths[(i + 1) * width + j + 1] + 1
+                } else {
+                    lengths[(i + 1) * width + j].max(lengths[i * width + j + 1])
+                };
+            }
+        }
+
+        let (mut i, mut j) = (0, 0);
+        while i < a.len() && j < b.len() {
+            if a[i] == b[j] {
+                ops.push(Op::Same);
+                i += 1;
+                j += 1;
+            } else if lengths[(i + 1) * width + j] >= lengths[i * width + j + 1] {
+                ops.push(Op::Removed);
+                i += 1;
+            } else {
+                ops.push(Op::Added);
+                j += 1;
+            }
+        }
+        ops.extend(std::iter::repeat_n(Op::Removed, a.len() - i));
+        ops.extend(std::iter::repeat_n(Op::Added, b.len() - j));
+    }
+
+    ops.extend(std::iter::repeat_n(Op::Same, suffix));
+    ops
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn equal_texts_have_no_hunks() {
+        assert!(unified("a\nb\n", "a\nb\n").is_empty());
+        assert!(unified("", "").is_empty());
+    }
+
+    #[test]
+    fn close_changes_share_a_hunk() {
+        let old: String = (1..=20).map(|n| format!("{}\n", n)).collect();
+        let new: String = (1..=20)
+            .filter_map(|n| match n {
+                5 => Some("five\n".to_string()),
+                9 => None,
+                18 => Some("eighteen\n".to_string()),
+                n => Some(format!("{}\n", n)),
+            })
+            .collect();
+
+        // The same hunks as diff -U3 gives.
+        let expected = [
+            "@@ -2,11 +2,10 @@",
+            " 2",
+            " 3",
+            " 4",
+            "-5",
+            "+five",
+            " 6",
+            " 7",
+            " 8",
+            "-9",
+            " 10",
+            " 11",
+            " 12",
+            "@@ -15,6 +14,6 @@",
+            " 15",
+            " 16",
+            " 17",
+            "-18",
+            "+eighteen",
+            " 19",
+            " 20",
+        ];
+        assert_eq!(unified(&old, &new), expected);
+    }
+
+    #[test]
+    fn empty_ranges_are_numbered_after_the_line_before() {
+        assert_eq!(unified("", "a\nb\n"), ["@@ -0,0 +1,2 @@", "+a", "+b"]);
+        assert_eq!(unified("a\nb\n", "a\n"), ["@@ -1,2 +1,1 @@", " a", "-b"]);
+    }
+}
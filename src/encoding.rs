@@ -0,0 +1,302 @@
+use encoding_rs::{Encoding, UTF_16BE, UTF_16LE, UTF_8, WINDOWS_1252};
+
+// Files are decoded into the UTF-8 buffer when they are opened, and encoded
+// back into the encoding they came in when they are saved. The encoding of a
+// file is taken from its byte order mark, and without one it is guessed:
+// UTF-16 when every other byte is mostly NUL, UTF-8 when the bytes are valid
+// UTF-8, and windows-1252 otherwise, which decodes any bytes, and is what most
+// legacy 8-bit text in Western languages turns out to be.
+
+// The number of bytes looked at to guess UTF-16 without a byte order mark.
+const UTF16_SAMPLE: usize = 4096;
+// The number of bytes looked at to tell binary files from text.
+const BINARY_SAMPLE: usize = 8192;
+
+/// The encoding of a file, and whether it starts with a byte order mark.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FileEncoding {
+    pub encoding: &'static Encoding,
+    pub bom: bool,
+}
+
+impl Default for FileEncoding {
+    fn default() -> FileEncoding {
+        FileEncoding {
+            encoding: UTF_8,
+            bom: false,
+        }
+    }
+}
+
+impl std::fmt::Display for FileEncoding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.bom {
+            true => write!(f, "{} BOM", self.encoding.name()),
+            false => f.write_str(self.encoding.name()),
+        }
+    }
+}
+
+// Parses an encoding label such as "utf-8", "latin1" or "utf-8 bom".
+// UTF-16 always gets a byte order mark, as it is hard to read without one,
+// and the other encodings never have one.
+impl std::str::FromStr for FileEncoding {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<FileEncoding, String> {
+        let label = s.trim().to_lowercase();
+        let (label, bom) = match label
+            .strip_suffix(" bom")
+            .or_else(|| label.strip_suffix("-bom"))
+        {
+            Some(label) => (label, true),
+            None => (label.as_str(), false),
+        };
+
+        let encoding = Encoding::for_label(label.as_bytes())
+            .filter(|encoding| is_supported(encoding))
+            .ok_or_else(|| format!("Unknown encoding: {}", s.trim()))?;
+        Ok(FileEncoding {
+            encoding,
+            bom: bom && encoding == UTF_8 || is_utf16(encoding),
+        })
+    }
+}
+
+fn is_utf16(encoding: &'static Encoding) -> bool {
+    encoding == UTF_16LE || encoding == UTF_16BE
+}
+
+// Encodings that can be both read and written. The others, such as the
+// "replacement" encoding, only ever decode.
+fn is_supported(encoding: &'static Encoding) -> bool {
+    encoding.output_encoding() == encoding || is_utf16(encoding)
+}
+
+/// Decodes the bytes of a file in the encoding guessed from them.
+pub fn decode_detected(bytes: &[u8]) -> (String, FileEncoding) {
+    if let Some(text) = decode(bytes, detect(bytes)) {
+        return text;
+    }
+
+    // A guess of UTF-16 can turn out wrong, windows-1252 never fails.
+    let fallback = FileEncoding {
+        encoding: WINDOWS_1252,
+        bom: false,
+    };
+    (
+        WINDOWS_1252
+            .decode_without_bom_handling(bytes)
+            .0
+            .into_owned(),
+        fallback,
+    )
+}
+
+/// Decodes bytes in an encoding. A byte order mark of the encoding is
+/// removed and remembered. Returns None when the bytes are not valid in it.
+pub fn decode(bytes: &[u8], encoding: FileEncoding) -> Option<(String, FileEncoding)> {
+    let (bytes, bom) = match Encoding::for_bom(bytes) {
+        Some((bom_encoding, len)) if bom_encoding == encoding.encoding => (&bytes[len..], true),
+        _ => (bytes, false),
+    };
+
+    let text = encoding
+        .encoding
+        .decode_without_bom_handling_and_without_replacement(bytes)?;
+
+    Some((
+        text.into_owned(),
+        FileEncoding {
+            encoding: encoding.encoding,
+            bom,
+        },
+    ))
+}
+
+/// Whether bytes look like a binary file rather than text: there is a NUL
+/// in the first bytes, and it is not UTF-16, which has them all over.
+pub fn is_binary(bytes: &[u8]) -> bool {
+    let sample = &bytes[..bytes.len().min(BINARY_SAMPLE)];
+    sample.contains(&0) && Encoding::for_bom(bytes).is_none() && detect_utf16(bytes).is_none()
+}
+
+fn detect(bytes: &[u8]) -> FileEncoding {
+    if let Some((encoding, _)) = Encoding::for_bom(bytes) {
+        return FileEncoding {
+            encoding,
+            bom: true,
+        };
+    }
+
+    // NUL is valid UTF-8, so UTF-16 is looked for first when there are any.
+    let sample = &bytes[..bytes.len().min(UTF16_SAMPLE)];
+    let utf16 = match sample.contains(&0) {
+        true => detect_utf16(bytes),
+        false => None,
+    };
+    let encoding = match utf16 {
+        Some(encoding) => encoding,
+        None if std::str::from_utf8(bytes).is_ok() => UTF_8,
+        None => WINDOWS_1252,
+    };
+
+    FileEncoding {
+        encoding,
+        bom: false,
+    }
+}
+
+// Text that is mostly ASCII has a NUL in every other byte in UTF-16,
+// the high byte of each unit, which comes second in little endian.
+fn detect_utf16(bytes: &[u8]) -> Option<&'static Encoding> {
+    let sample = &bytes[..bytes.len().min(UTF16_SAMPLE)];
+    let units = sample.len() / 2;
+    if units == 0 || !bytes.len().is_multiple_of(2) {
+        return None;
+    }
+
+    let zeros = |offset: usize| {
+        sample
+            .iter()
+            .skip(offset)
+            .step_by(2)
+            .filter(|b| **b == 0)
+            .count()
+    };
+    let (even, odd) = (zeros(0), zeros(1));
+
+    if odd * 10 >= units * 4 && even * 10 < units {
+        Some(UTF_16LE)
+    } else if even * 10 >= units * 4 && odd * 10 < units {
+        Some(UTF_16BE)
+    } else {
+        None
+    }
+}
+
+/// Encodes text in an encoding, with its byte order mark if it has one.
+/// Returns the first char that the encoding has no bytes for.
+pub fn encode(text: &str, encoding: FileEncoding) -> Result<Vec<u8>, char> {
+    let mut bytes = Vec::with_capacity(text.len() + 3);
+
+    if encoding.encoding == UTF_8 {
+        if encoding.bom {
+            bytes.extend_from_slice(b"\xEF\xBB\xBF");
+        }
+        bytes.extend_from_slice(text.as_bytes());
+    } else if is_utf16(encoding.encoding) {
+        // encoding_rs only decodes UTF-16, so it is encoded here.
+        let little_endian = encoding.encoding == UTF_16LE;
+        if encoding.bom {
+            bytes.extend_from_slice(if little_endian {
+                b"\xFF\xFE"
+            } else {
+                b"\xFE\xFF"
+            });
+        }
+        for unit in text.encode_utf16() {
+            bytes.extend_from_slice(&match little_endian {
+                true => unit.to_le_bytes(),
+                false => unit.to_be_bytes(),
+            });
+        }
+    } else {
+        let (encoded, _, had_errors) = encoding.encoding.encode(text);
+        if had_errors {
+            // encoding_rs writes chars it cannot encode as "&#...;",
+            // which would change the text, so the char is looked for instead.
+            let mut utf8 = [0; 4];
+            let unmappable = text
+                .chars()
+                .find(|c| encoding.encoding.encode(c.encode_utf8(&mut utf8)).2)
+                .unwrap_or(char::REPLACEMENT_CHARACTER);
+            return Err(unmappable);
+        }
+        bytes.extend_from_slice(&encoded);
+    }
+
+    Ok(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encoding(label: &str) -> FileEncoding {
+        label.parse().unwrap()
+    }
+
+    // Encoding the decoded text gives back the same bytes.
+    fn assert_round_trip(bytes: &[u8], expected: &str) {
+        let (text, detected) = decode_detected(bytes);
+        assert_eq!(detected.to_string(), expected);
+        assert_eq!(encode(&text, detected).unwrap(), bytes);
+    }
+
+    #[test]
+    fn round_trips_detected_encodings() {
+        assert_round_trip(b"plain ascii\n", "UTF-8");
+        assert_round_trip("caf\u{e9} \u{20ac}\n".as_bytes(), "UTF-8");
+        assert_round_trip(b"\xEF\xBB\xBFwith bom\n", "UTF-8 BOM");
+        assert_round_trip(b"caf\xe9 \x80\n", "windows-1252");
+        assert_round_trip(b"\xFF\xFEh\0i\0", "UTF-16LE BOM");
+        assert_round_trip(b"\xFE\xFF\0h\0i", "UTF-16BE BOM");
+    }
+
+    #[test]
+    fn detects_utf16_without_bom() {
+        // NUL is valid UTF-8, so this would be taken for UTF-8 if it were tried first.
+        let le: Vec<u8> = "hello\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        let be: Vec<u8> = "hello\n"
+            .encode_utf16()
+            .flat_map(u16::to_be_bytes)
+            .collect();
+
+        assert!(!is_binary(&le));
+        assert!(!is_binary(&be));
+        assert_round_trip(&le, "UTF-16LE");
+        assert_round_trip(&be, "UTF-16BE");
+        assert_eq!(decode_detected(&le).0, "hello\n");
+    }
+
+    #[test]
+    fn tells_binary_from_text() {
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\0\0\0\0\0\0\0\0\0\x03\0"));
+        assert!(!is_binary(b"no nul here"));
+        assert!(!is_binary(b"\xFF\xFEh\0i\0"));
+    }
+
+    #[test]
+    fn parses_labels() {
+        assert_eq!(encoding("latin1").to_string(), "windows-1252");
+        assert_eq!(encoding("utf-8 bom").to_string(), "UTF-8 BOM");
+        assert_eq!(encoding("UTF-8-BOM").to_string(), "UTF-8 BOM");
+        // UTF-16 always gets a byte order mark, other encodings never do.
+        assert_eq!(encoding("utf-16be").to_string(), "UTF-16BE BOM");
+        assert_eq!(encoding("latin1 bom").to_string(), "windows-1252");
+        assert!("replacement".parse::<FileEncoding>().is_err());
+        assert!("nope".parse::<FileEncoding>().is_err());
+    }
+
+    #[test]
+    fn decoding_is_strict() {
+        assert_eq!(decode(b"\xff", FileEncoding::default()), None);
+        assert_eq!(
+            decode(b"\xEF\xBB\xBFhi", FileEncoding::default()),
+            Some(("hi".to_string(), encoding("utf-8 bom")))
+        );
+    }
+
+    #[test]
+    fn reports_unencodable_chars() {
+        assert_eq!(
+            encode("a\u{20ac}b", encoding("latin1")),
+            Ok(b"a\x80b".to_vec())
+        );
+        assert_eq!(encode("a\u{2713}b", encoding("latin1")), Err('\u{2713}'));
+    }
+}
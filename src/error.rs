@@ -0,0 +1,79 @@
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// The errors of opening and saving files.
+/// They are shown to the user, so their messages name the file they are about.
+#[derive(Debug)]
+pub enum Error {
+    Io {
+        path: Option<PathBuf>,
+        source: std::io::Error,
+    },
+    // Saving a file that was never given a path.
+    NoPath,
+    IsDirectory(PathBuf),
+    // A path without a file name, such as "/" or "dir/..".
+    NotAFile(PathBuf),
+    // The bytes of a file are not valid in the encoding it is opened with.
+    Decode {
+        path: PathBuf,
+        encoding: &'static str,
+    },
+    // A char of the text that the encoding of the file has no bytes for.
+    Encode {
+        path: PathBuf,
+        encoding: &'static str,
+        c: char,
+    },
+}
+
+impl Error {
+    // Wraps an io error with the path it happened on.
+    pub fn io(path: &Path, source: std::io::Error) -> Error {
+        Error::Io {
+            path: Some(path.to_path_buf()),
+            source,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Io {
+                path: Some(path),
+                source,
+            } => write!(f, "{}: {}", path.display(), source),
+            Error::Io { path: None, source } => write!(f, "{}", source),
+            Error::NoPath => write!(f, "the file has no path"),
+            Error::IsDirectory(path) => write!(f, "{} is a directory", path.display()),
+            Error::NotAFile(path) => write!(f, "{} is not a file name", path.display()),
+            Error::Decode { path, encoding } => {
+                write!(f, "{} is not valid {}", path.display(), encoding)
+            }
+            Error::Encode { path, encoding, c } => write!(
+                f,
+                "{}: {:?} (U+{:04X}) cannot be saved as {}",
+                path.display(),
+                c,
+                *c as u32,
+                encoding
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(source: std::io::Error) -> Error {
+        Error::Io { path: None, source }
+    }
+}
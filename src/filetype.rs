@@ -0,0 +1,81 @@
+use std::path::Path;
+
+// Known file types by extension.
+const EXTENSIONS: &[(&str, &str)] = &[
+    ("rs", "Rust"),
+    ("toml", "TOML"),
+    ("md", "Markdown"),
+    ("markdown", "Markdown"),
+    ("txt", "Plain Text"),
+    ("json", "JSON"),
+    ("yaml", "YAML"),
+    ("yml", "YAML"),
+    ("py", "Python"),
+    ("js", "JavaScript"),
+    ("ts", "TypeScript"),
+    ("c", "C"),
+    ("h", "C"),
+    ("cpp", "C++"),
+    ("hpp", "C++"),
+    ("cc", "C++"),
+    ("go", "Go"),
+    ("java", "Java"),
+    ("sh", "Shell"),
+    ("bash", "Shell"),
+    ("html", "HTML"),
+    ("css", "CSS"),
+    ("xml", "XML"),
+    ("sql", "SQL"),
+    ("log", "Log"),
+];
+
+// Known file types by file name, for files without a useful extension.
+const FILE_NAMES: &[(&str, &str)] = &[
+    ("Makefile", "Makefile"),
+    ("Dockerfile", "Dockerfile"),
+    ("Cargo.lock", "TOML"),
+    (".gitignore", "Git Ignore"),
+];
+
+// Known interpreters in a "#!" line.
+const INTERPRETERS: &[(&str, &str)] = &[
+    ("python", "Python"),
+    ("bash", "Shell"),
+    ("sh", "Shell"),
+    ("node", "JavaScript"),
+];
+
+/// Guesses the type of a file from its name, or from a "#!" first line.
+pub fn detect(name: &Path, first_line: &str) -> &'static str {
+    let file_name = name.file_name().and_then(|n| n.to_str()).unwrap_or("");
+    if let Some((_, file_type)) = FILE_NAMES.iter().find(|(n, _)| *n == file_name) {
+        return file_type;
+    }
+
+    let extension = name.extension().and_then(|e| e.to_str()).unwrap_or("");
+    if let Some((_, file_type)) = EXTENSIONS
+        .iter()
+        .find(|(e, _)| e.eq_ignore_ascii_case(extension))
+    {
+        return file_type;
+    }
+
+    // In "#!/usr/bin/env python3" and "#!/bin/sh -e" the interpreter is
+    // the first program named that is not env.
+    if let Some(shebang) = first_line.strip_prefix("#!") {
+        let interpreter = shebang
+            .split_whitespace()
+            .map(|word| word.rsplit('/').next().unwrap_or(word))
+            .find(|word| *word != "env")
+            .unwrap_or("");
+
+        if let Some((_, file_type)) = INTERPRETERS
+            .iter()
+            .find(|(i, _)| interpreter.starts_with(i))
+        {
+            return file_type;
+        }
+    }
+
+    "Plain Text"
+}
@@ -0,0 +1,313 @@
+use std::ops::Range;
+
+use crate::buffer::TextBuffer;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EditKind {
+    Insert,
+    Remove,
+}
+
+/// A single change to a buffer.
+/// Both inserts and removes keep their text, so an edit can always be reversed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Edit {
+    pub kind: EditKind,
+    pub char_idx: usize,
+    pub text: String,
+}
+
+impl Edit {
+    pub fn insert(char_idx: usize, text: &str) -> Edit {
+        Edit {
+            kind: EditKind::Insert,
+            char_idx,
+            text: text.to_string(),
+        }
+    }
+
+    // The removed text is read from the buffer before it is gone.
+    pub fn remove(buffer: &TextBuffer, char_range: Range<usize>) -> Edit {
+        Edit {
+            kind: EditKind::Remove,
+            char_idx: char_range.start,
+            text: buffer.slice(char_range).to_string(),
+        }
+    }
+
+    pub fn len_chars(&self) -> usize {
+        self.text.chars().count()
+    }
+
+    pub fn apply(&self, buffer: &mut TextBuffer) {
+        match self.kind {
+            EditKind::Insert => buffer.insert(self.char_idx, &self.text),
+            EditKind::Remove => buffer.remove(self.char_idx..self.char_idx + self.len_chars()),
+        }
+    }
+
+    pub fn inverse(&self) -> Edit {
+        let kind = match self.kind {
+            EditKind::Insert => EditKind::Remove,
+            EditKind::Remove => EditKind::Insert,
+        };
+
+        Edit {
+            kind,
+            char_idx: self.char_idx,
+            text: self.text.clone(),
+        }
+    }
+}
+
+/// A group of edits that is undone and redone as one step.
+/// The cursor positions are char indices into the buffer.
+///
+/// The id names the buffer contents after the transaction. It changes
+/// whenever the transaction changes, so it can be compared to a saved revision.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Transaction {
+    pub id: u64,
+    pub edits: Vec<Edit>,
+    pub cursor_before: usize,
+    pub cursor_after: usize,
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct History {
+    pub undo_stack: Vec<Transaction>,
+    pub redo_stack: Vec<Transaction>,
+
+    // Whether the last transaction can still be extended by typing.
+    typing: bool,
+    // While a transaction is open, every edit is added to it.
+    open: bool,
+    // The last id given to a transaction. Revision 0 is the unedited buffer.
+    last_id: u64,
+}
+
+impl History {
+    pub fn new() -> History {
+        History::default()
+    }
+
+    pub fn new_id(&mut self) -> u64 {
+        self.last_id += 1;
+        self.last_id
+    }
+
+    /// The revision of the buffer contents, which is the id of the last applied transaction.
+    pub fn revision(&self) -> u64 {
+        self.undo_stack
+            .last()
+            .map_or(0, |transaction| transaction.id)
+    }
+
+    /// Starts a transaction. Every edit until `commit` becomes a single undo step.
+    pub fn begin(&mut self, cursor: usize) {
+        let id = self.new_id();
+        self.undo_stack.push(Transaction {
+            id,
+            edits: Vec::new(),
+            cursor_before: cursor,
+            cursor_after: cursor,
+        });
+        self.open = true;
+        self.typing = false;
+    }
+
+    pub fn commit(&mut self) {
+        self.open = false;
+
+        if matches!(self.undo_stack.last(), Some(t) if t.edits.is_empty()) {
+            self.undo_stack.pop();
+        }
+    }
+
+    // Typing is grouped until the cursor moves or a different kind of edit happens.
+    pub fn break_group(&mut self) {
+        self.typing = false;
+    }
+
+    pub fn record(&mut self, edit: Edit, cursor_before: usize, cursor_after: usize, typing: bool) {
+        self.redo_stack.clear();
+
+        let extends_typing = typing
+            && self.typing
+            && matches!(
+                self.undo_stack.last().and_then(|t| t.edits.last()),
+                Some(last) if last.kind == EditKind::Insert
+                    && last.char_idx + last.len_chars() == edit.char_idx
+            );
+
+        let id = self.new_id();
+        match self.undo_stack.last_mut() {
+            // Typed characters are merged into the previous insert.
+            Some(transaction) if extends_typing => {
+                if let Some(last) = transaction.edits.last_mut() {
+                    last.text.push_str(&edit.text);
+                }
+                transaction.id = id;
+                transaction.cursor_after = cursor_after;
+            }
+            Some(transaction) if self.open => {
+                transaction.edits.push(edit);
+                transaction.id = id;
+                transaction.cursor_after = cursor_after;
+            }
+            _ => self.undo_stack.push(Transaction {
+                id,
+                edits: vec![edit],
+                cursor_before,
+                cursor_after,
+            }),
+        }
+
+        self.typing = typing && !self.open;
+    }
+
+    /// Moves the last transaction to the redo stack.
+    /// Returns the edits that revert it, in the order they have to be applied,
+    /// and where the cursor should go.
+    pub fn undo(&mut self) -> Option<(Vec<Edit>, usize)> {
+        let transaction = self.undo_stack.pop()?;
+        let edits = transaction.edits.iter().rev().map(Edit::inverse).collect();
+
+        let cursor = transaction.cursor_before;
+        self.redo_stack.push(transaction);
+        self.typing = false;
+
+        Some((edits, cursor))
+    }
+
+    /// Moves the last undone transaction back to the undo stack.
+    /// Returns the edits that reapply it and where the cursor should go.
+    pub fn redo(&mut self) -> Option<(Vec<Edit>, usize)> {
+        let transaction = self.redo_stack.pop()?;
+        let edits = transaction.edits.clone();
+
+        let cursor = transaction.cursor_after;
+        self.undo_stack.push(transaction);
+        self.typing = false;
+
+        Some((edits, cursor))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Records typing one char at a time, from char_idx on.
+    fn type_text(history: &mut History, char_idx: usize, text: &str) {
+        for (i, c) in text.chars().enumerate() {
+            let edit = Edit::insert(char_idx + i, &c.to_string());
+            history.record(edit, char_idx + i, char_idx + i + 1, true);
+        }
+    }
+
+    #[test]
+    fn typing_is_one_step_until_the_group_breaks() {
+        let mut history = History::new();
+        type_text(&mut history, 0, "hello");
+        assert_eq!(history.undo_stack.len(), 1);
+        assert_eq!(history.undo_stack[0].edits, [Edit::insert(0, "hello")]);
+
+        history.break_group();
+        type_text(&mut history, 5, " world");
+        assert_eq!(history.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn typing_elsewhere_starts_a_new_step() {
+        let mut history = History::new();
+        type_text(&mut history, 0, "ab");
+        type_text(&mut history, 0, "c");
+        assert_eq!(history.undo_stack.len(), 2);
+
+        // Edits that are not typing are never merged.
+        let mut history = History::new();
+        history.record(Edit::insert(0, "a"), 0, 1, false);
+        history.record(Edit::insert(1, "b"), 1, 2, false);
+        assert_eq!(history.undo_stack.len(), 2);
+    }
+
+    #[test]
+    fn transactions_group_edits_and_empty_ones_are_dropped() {
+        let mut history = History::new();
+        history.begin(0);
+        history.record(Edit::insert(0, "a"), 0, 1, false);
+        history.record(Edit::insert(1, "b"), 1, 2, true);
+        history.commit();
+        assert_eq!(history.undo_stack.len(), 1);
+        assert_eq!(history.undo_stack[0].edits.len(), 2);
+
+        let revision = history.revision();
+        history.begin(2);
+        history.commit();
+        assert_eq!(history.undo_stack.len(), 1);
+        assert_eq!(history.revision(), revision);
+    }
+
+    #[test]
+    fn undo_reverses_the_edits_in_reverse_order() {
+        let mut history = History::new();
+        history.begin(0);
+        history.record(Edit::insert(0, "a"), 0, 1, false);
+        history.record(Edit::insert(1, "b"), 1, 2, false);
+        history.commit();
+
+        let (edits, cursor) = history.undo().unwrap();
+        assert_eq!(
+            edits,
+            [
+                Edit::insert(1, "b").inverse(),
+                Edit::insert(0, "a").inverse()
+            ]
+        );
+        assert_eq!(cursor, 0);
+
+        let (edits, cursor) = history.redo().unwrap();
+        assert_eq!(edits, [Edit::insert(0, "a"), Edit::insert(1, "b")]);
+        assert_eq!(cursor, 2);
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn a_new_edit_clears_redo() {
+        let mut history = History::new();
+        history.record(Edit::insert(0, "a"), 0, 1, false);
+        history.undo();
+        assert_eq!(history.redo_stack.len(), 1);
+
+        history.record(Edit::insert(0, "b"), 0, 1, false);
+        assert!(history.redo_stack.is_empty());
+        assert!(history.redo().is_none());
+    }
+
+    #[test]
+    fn undo_returns_to_the_saved_revision() {
+        let mut history = History::new();
+        assert_eq!(history.revision(), 0);
+
+        history.record(Edit::insert(0, "a"), 0, 1, false);
+        let saved = history.revision();
+        history.record(Edit::insert(1, "b"), 1, 2, false);
+        assert_ne!(history.revision(), saved);
+
+        history.undo();
+        assert_eq!(history.revision(), saved);
+        history.undo();
+        assert_eq!(history.revision(), 0);
+        history.redo();
+        assert_eq!(history.revision(), saved);
+
+        // Typing more into a step gives it a new revision.
+        let mut history = History::new();
+        type_text(&mut history, 0, "a");
+        let saved = history.revision();
+        type_text(&mut history, 1, "b");
+        assert_eq!(history.undo_stack.len(), 1);
+        assert_ne!(history.revision(), saved);
+    }
+}
@@ -0,0 +1,282 @@
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+use crate::buffer::TextBuffer;
+use crate::history::{Edit, EditKind, History, Transaction};
+
+// The history store keeps the undo and redo stacks of a file between sessions.
+// Each file gets one history file in the state directory, named after a hash of
+// its canonical path. The history is only loaded again when the hash of the
+// file contents still matches the hash stored next to it.
+
+const HEADER: &str = "rustex-history 1";
+
+// $XDG_STATE_HOME/rustex, falling back to ~/.local/state/rustex.
+pub fn rustex_state_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local/state"),
+    };
+
+    Some(base.join("rustex"))
+}
+
+pub fn state_dir() -> Option<PathBuf> {
+    Some(rustex_state_dir()?.join("history"))
+}
+
+// FNV-1a is used instead of the std hasher, because it has to give the
+// same result across builds for the stored hashes to stay valid.
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes.iter().fold(hash, |hash, byte| {
+        (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+    })
+}
+
+const FNV_OFFSET: u64 = 0xcbf29ce484222325;
+
+pub fn content_hash(buffer: &TextBuffer) -> u64 {
+    buffer
+        .chunks()
+        .fold(FNV_OFFSET, |hash, chunk| fnv1a(hash, chunk.as_bytes()))
+}
+
+// The name of the stored file of a canonical path.
+pub fn path_hash(canonical: &str) -> String {
+    format!("{:016x}", fnv1a(FNV_OFFSET, canonical.as_bytes()))
+}
+
+fn history_path(path: &Path) -> Option<(PathBuf, String)> {
+    let canonical = path.canonicalize().ok()?.to_string_lossy().to_string();
+
+    Some((state_dir()?.join(path_hash(&canonical)), canonical))
+}
+
+/// Writes the history of a file to the store.
+/// `buffer` must hold the contents that were just saved to `path`.
+pub fn save(path: &Path, buffer: &TextBuffer, history: &History) -> Result<(), std::io::Error> {
+    let (history_path, canonical) = history_path(path)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no state directory"))?;
+    std::fs::create_dir_all(history_path.parent().unwrap())?;
+
+    let mut writer = BufWriter::new(std::fs::File::create(history_path)?);
+    write_history(&mut writer, &canonical, buffer, history)?;
+    writer.flush()
+}
+
+/// Reads the stored history of a file.
+/// Returns None when there is no history, or when it belongs to other contents.
+pub fn load(path: &Path, buffer: &TextBuffer) -> Option<History> {
+    let (history_path, canonical) = history_path(path)?;
+    let mut reader = BufReader::new(std::fs::File::open(history_path).ok()?);
+
+    read_history(&mut reader, &canonical, buffer)
+}
+
+fn write_history<W: Write>(
+    writer: &mut W,
+    canonical: &str,
+    buffer: &TextBuffer,
+    history: &History,
+) -> Result<(), std::io::Error> {
+    writeln!(writer, "{}", HEADER)?;
+    writeln!(writer, "{}", canonical)?;
+    writeln!(writer, "{:016x}", content_hash(buffer))?;
+
+    for stack in [&history.undo_stack, &history.redo_stack] {
+        writeln!(writer, "{}", stack.len())?;
+        for transaction in stack {
+            write_transaction(writer, transaction)?;
+        }
+    }
+
+    Ok(())
+}
+
+fn read_history<R: BufRead>(
+    reader: &mut R,
+    canonical: &str,
+    buffer: &TextBuffer,
+) -> Option<History> {
+    if read_line(reader)? != HEADER || read_line(reader)? != canonical {
+        return None;
+    }
+
+    let hash = u64::from_str_radix(&read_line(reader)?, 16).ok()?;
+    if hash != content_hash(buffer) {
+        return None;
+    }
+
+    let mut history = History::new();
+    history.undo_stack = read_stack(reader, &mut history)?;
+    history.redo_stack = read_stack(reader, &mut history)?;
+
+    Some(history)
+}
+
+// Reads a transaction count followed by that many transactions.
+fn read_stack<R: BufRead>(reader: &mut R, history: &mut History) -> Option<Vec<Transaction>> {
+    let count: usize = read_line(reader)?.parse().ok()?;
+
+    let mut stack = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut transaction = read_transaction(reader)?;
+        transaction.id = history.new_id();
+        stack.push(transaction);
+    }
+
+    Some(stack)
+}
+
+// A transaction is written as "cursor_before cursor_after edit_count",
+// transaction ids are not stored and are given out again when loading,
+// followed by "kind char_idx byte_len" and the raw text for every edit.
+fn write_transaction<W: Write>(
+    writer: &mut W,
+    transaction: &Transaction,
+) -> Result<(), std::io::Error> {
+    writeln!(
+        writer,
+        "{} {} {}",
+        transaction.cursor_before,
+        transaction.cursor_after,
+        transaction.edits.len()
+    )?;
+
+    for edit in &transaction.edits {
+        let kind = match edit.kind {
+            EditKind::Insert => 'i',
+            EditKind::Remove => 'r',
+        };
+        writeln!(writer, "{} {} {}", kind, edit.char_idx, edit.text.len())?;
+        writer.write_all(edit.text.as_bytes())?;
+        writeln!(writer)?;
+    }
+
+    Ok(())
+}
+
+fn read_transaction<R: BufRead>(reader: &mut R) -> Option<Transaction> {
+    let header = read_line(reader)?;
+    let mut fields = header.split(' ').map(|field| field.parse::<usize>().ok());
+    let cursor_before = fields.next()??;
+    let cursor_after = fields.next()??;
+    let edit_count = fields.next()??;
+
+    let mut edits = Vec::with_capacity(edit_count);
+    for _ in 0..edit_count {
+        let header = read_line(reader)?;
+        let mut fields = header.split(' ');
+        let kind = match fields.next()? {
+            "i" => EditKind::Insert,
+            "r" => EditKind::Remove,
+            _ => return None,
+        };
+        let char_idx = fields.next()?.parse().ok()?;
+        let len = fields.next()?.parse().ok()?;
+
+        let mut text = vec![0; len];
+        reader.read_exact(&mut text).ok()?;
+        read_line(reader)?;
+
+        edits.push(Edit {
+            kind,
+            char_idx,
+            text: String::from_utf8(text).ok()?,
+        });
+    }
+
+    Some(Transaction {
+        id: 0,
+        edits,
+        cursor_before,
+        cursor_after,
+    })
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Option<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+
+    Some(line.trim_end_matches('\n').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // A history of two transactions over several lines, the last one undone.
+    fn edited() -> (TextBuffer, History) {
+        let mut buffer = TextBuffer::from("first\nsecond\n");
+        let mut history = History::new();
+
+        let edit = Edit::insert(6, "inserted\nlines ");
+        edit.apply(&mut buffer);
+        history.record(edit, 6, 21, false);
+
+        let edit = Edit::remove(&buffer, 0..6);
+        edit.apply(&mut buffer);
+        history.record(edit, 6, 0, false);
+
+        let (edits, _) = history.undo().unwrap();
+        for edit in edits {
+            edit.apply(&mut buffer);
+        }
+
+        (buffer, history)
+    }
+
+    fn stored(buffer: &TextBuffer, history: &History) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_history(&mut bytes, "/notes.txt", buffer, history).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trips_both_stacks() {
+        let (buffer, history) = edited();
+        let bytes = stored(&buffer, &history);
+        let loaded = read_history(&mut bytes.as_slice(), "/notes.txt", &buffer).unwrap();
+
+        // The ids are given out again, the rest is as it was.
+        let without_ids = |stack: &[Transaction]| -> Vec<Transaction> {
+            stack
+                .iter()
+                .map(|transaction| Transaction {
+                    id: 0,
+                    ..transaction.clone()
+                })
+                .collect()
+        };
+        assert_eq!(
+            without_ids(&loaded.undo_stack),
+            without_ids(&history.undo_stack)
+        );
+        assert_eq!(
+            without_ids(&loaded.redo_stack),
+            without_ids(&history.redo_stack)
+        );
+        assert_ne!(loaded.revision(), 0);
+    }
+
+    #[test]
+    fn ignores_history_of_other_contents_or_files() {
+        let (buffer, history) = edited();
+        let bytes = stored(&buffer, &history);
+
+        let changed = TextBuffer::from("changed elsewhere\n");
+        assert!(read_history(&mut bytes.as_slice(), "/notes.txt", &changed).is_none());
+        assert!(read_history(&mut bytes.as_slice(), "/other.txt", &buffer).is_none());
+    }
+
+    #[test]
+    fn ignores_truncated_history() {
+        let (buffer, history) = edited();
+        let bytes = stored(&buffer, &history);
+
+        let truncated = &bytes[..bytes.len() - 4];
+        assert!(read_history(&mut &truncated[..], "/notes.txt", &buffer).is_none());
+    }
+}
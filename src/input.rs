@@ -17,18 +17,28 @@ pub fn input_handler(
             .unwrap_or_else(|| std::time::Duration::from_secs(1));
 
         // poll for events
-        if event::poll(timeout).unwrap() {
-            let event = event::read().unwrap();
-            let result = event_sender.send(InputEvent::InputEvent(event));
+        // When the terminal is gone, e.g. after a hangup, the thread stops,
+        // and the main loop sees the channel close.
+        let event = match event::poll(timeout) {
+            Ok(true) => match event::read() {
+                Ok(event) => Some(event),
+                Err(_) => break,
+            },
+            Ok(false) => None,
+            Err(_) => break,
+        };
 
-            if result.is_err() {
+        if let Some(event) = event {
+            if event_sender.send(InputEvent::InputEvent(event)).is_err() {
                 break;
             }
         }
 
         // send tick event
         if last_tick.elapsed() >= tick_rate {
-            event_sender.send(InputEvent::Tick).unwrap();
+            if event_sender.send(InputEvent::Tick).is_err() {
+                break;
+            }
             last_tick = std::time::Instant::now();
         }
     }
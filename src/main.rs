@@ -1,6 +1,17 @@
-#[cfg(feature = "termion")]
-mod events;
+mod buffer;
+mod clipboard;
+mod diff;
+mod encoding;
+mod error;
+mod filetype;
+mod history;
+mod history_store;
+mod markers;
+mod save;
+mod swap;
+mod terminal_guard;
 mod ui;
+mod watch;
 
 #[allow(dead_code)]
 mod app;
@@ -11,13 +22,15 @@ use argh::FromArgs;
 
 use crossterm::event;
 use crossterm::event::Event;
-use crossterm::event::KeyCode;
 
-use crossterm::terminal;
+use signal_hook::consts::{SIGHUP, SIGTERM};
 
 use std::error::Error;
+use std::io::Write;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc;
 use std::sync::mpsc::Receiver;
+use std::sync::Arc;
 
 use tui::backend::CrosstermBackend;
 use tui::Terminal;
@@ -35,6 +48,42 @@ struct Cli {
     /// time in ms between two ticks.
     #[argh(option, default = "250")]
     tick_rate: u64,
+
+    /// keep undo history of saved files between sessions.
+    #[argh(switch)]
+    persistent_undo: bool,
+
+    /// line numbers in the gutter: absolute, relative or off.
+    #[argh(option, default = "app::LineNumbers::Absolute")]
+    line_numbers: app::LineNumbers,
+
+    /// comma separated status bar items: mode, file, modified, position,
+    /// lines, line-ending, encoding and file-type.
+    #[argh(option, default = "app::StatusBar::default()")]
+    status_bar: app::StatusBar,
+
+    /// how copied text reaches the system clipboard: none, osc52, command or auto.
+    #[argh(option, default = "clipboard::ClipboardBridge::None")]
+    clipboard: clipboard::ClipboardBridge,
+
+    /// keep the previous version of saved files: none, tilde (as "file~")
+    /// or dir (timestamped, in the backup directory).
+    #[argh(option, default = "save::BackupMode::None")]
+    backup: save::BackupMode,
+
+    /// the directory of --backup dir, by default ~/.local/state/rustex/backup.
+    #[argh(option)]
+    backup_dir: Option<std::path::PathBuf>,
+
+    /// write the stdin buffer ("-"), or else the file current when quitting,
+    /// to stdout on exit, to use rustex in a pipeline.
+    #[argh(switch)]
+    stdout: bool,
+
+    /// files to open. A file can be given as path:line:col, or after +line.
+    /// "-" reads stdin.
+    #[argh(positional)]
+    files: Vec<String>,
     // whether unicode symbols are used to improve the overall look of the app
     // #[argh(option, default = "true")]
     // enhanced_graphics: bool,
@@ -42,19 +91,31 @@ struct Cli {
 
 fn main() -> Result<(), Box<dyn Error>> {
     let mut app = app::App::new("Rustex");
-    let cli: Cli = argh::from_env();
-
-    terminal::enable_raw_mode()?;
+    let cli = parse_cli();
+    app.context.file_manager_window.persistent_history = cli.persistent_undo;
+    app.context.file_manager_window.backup = save::Backup {
+        mode: cli.backup,
+        dir: cli.backup_dir.clone(),
+    };
+    app.context.line_numbers = cli.line_numbers;
+    app.context.status_bar = cli.status_bar;
+    app.context
+        .file_manager_window
+        .clipboard
+        .replace(clipboard::Clipboard::new(cli.clipboard));
+    app.context.file_manager_window.stdout = cli.stdout;
+    app.open_targets(app::FileTarget::parse_args(&cli.files));
 
-    let mut stdout = std::io::stdout();
+    terminal_guard::install_panic_hook();
+    let guard = terminal_guard::TerminalGuard::enter()?;
 
-    crossterm::execute!(
-        stdout,
-        terminal::EnterAlternateScreen,
-        event::EnableMouseCapture
-    )?;
+    // SIGTERM and SIGHUP end the editor, after modified files are saved aside.
+    let exit_signal = Arc::new(AtomicUsize::new(0));
+    for signal in [SIGTERM, SIGHUP] {
+        signal_hook::flag::register_usize(signal, Arc::clone(&exit_signal), signal as usize)?;
+    }
 
-    let backend = CrosstermBackend::new(stdout);
+    let backend = CrosstermBackend::new(terminal_guard::output()?);
     let mut terminal = Terminal::new(backend)?;
     terminal.clear()?;
 
@@ -67,60 +128,138 @@ fn main() -> Result<(), Box<dyn Error>> {
         input::input_handler(tick_rate, event_sender);
     });
 
+    let mut emergency = None;
     loop {
-        handle_event(&event_receiver, &mut terminal, &mut app);
+        // The input thread stops when the terminal is gone.
+        if handle_event(&event_receiver, &mut app, tick_rate).is_err() {
+            emergency = Some("the terminal closed".to_string());
+            break;
+        }
 
-        terminal.draw(|frame| ui::draw(frame, &mut app))?;
+        match exit_signal.load(Ordering::Relaxed) as i32 {
+            0 => {}
+            SIGTERM => emergency = Some("received SIGTERM".to_string()),
+            SIGHUP => emergency = Some("received SIGHUP".to_string()),
+            signal => emergency = Some(format!("received signal {}", signal)),
+        }
 
-        if app.should_quit {
+        if app.should_quit || emergency.is_some() {
             break;
         }
+
+        terminal.draw(|frame| ui::draw(frame, &mut app))?;
+    }
+
+    drop(terminal);
+    drop(guard);
+    // Swap files still being written or removed are done before exiting.
+    app.context.file_manager_window.swap_writer.flush();
+
+    if let Some(reason) = emergency {
+        eprintln!("rustex: {}, saving modified files", reason);
+        for saved in app.context.file_manager_window.emergency_save() {
+            match saved {
+                Ok(path) => eprintln!("rustex: saved {}", path.display()),
+                Err(error) => eprintln!("rustex: could not save {}", error),
+            }
+        }
+        std::process::exit(1);
+    }
+
+    // Quitting may have made another file current, the piped one is written:
+    // the stdin buffer, or else the file that was current when quitting started.
+    if cli.stdout {
+        let file_manager = &mut app.context.file_manager_window;
+        let contents = match file_manager.file_list.iter().find(|file| file.piped) {
+            Some(file) => file.encoded()?,
+            None => file_manager.current_file().encoded()?,
+        };
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&contents)?;
+        stdout.flush()?;
     }
 
     Ok(())
 }
 
+// argh reads a lone "-" as an unknown option. It is swapped for a placeholder
+// while parsing, which no path can be as it contains a NUL.
+const STDIN_PLACEHOLDER: &str = "\0-";
+
+fn parse_cli() -> Cli {
+    let args: Vec<String> = std::env::args().collect();
+    let args: Vec<&str> = args
+        .iter()
+        .map(|arg| match arg.as_str() {
+            "-" => STDIN_PLACEHOLDER,
+            arg => arg,
+        })
+        .collect();
+
+    let command = args.first().copied().unwrap_or("rustex");
+    let command = std::path::Path::new(command)
+        .file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or(command);
+
+    match Cli::from_args(&[command], args.get(1..).unwrap_or(&[])) {
+        Ok(mut cli) => {
+            for file in cli.files.iter_mut() {
+                if file == STDIN_PLACEHOLDER {
+                    *file = "-".to_string();
+                }
+            }
+            cli
+        }
+        // The same as argh::from_env, for --help and parse errors.
+        Err(early_exit) => std::process::exit(match early_exit.status {
+            Ok(()) => {
+                println!("{}", early_exit.output);
+                0
+            }
+            Err(()) => {
+                eprintln!(
+                    "{}\nRun {} --help for more information.",
+                    early_exit.output, command
+                );
+                1
+            }
+        }),
+    }
+}
+
+// Waits up to timeout for an event, so the caller gets to check for signals
+// even when the input thread is stuck on a terminal that hung up.
+// Returns an error when the input thread has stopped.
 pub fn handle_event(
     event_receiver: &Receiver<InputEvent<Event>>,
-    terminal: &mut Terminal<CrosstermBackend<std::io::Stdout>>,
     app: &mut app::App,
-) {
-    let event_received = event_receiver.recv().unwrap();
+    timeout: std::time::Duration,
+) -> Result<(), mpsc::RecvError> {
+    let event_received = match event_receiver.recv_timeout(timeout) {
+        Ok(event_received) => event_received,
+        Err(mpsc::RecvTimeoutError::Timeout) => return Ok(()),
+        Err(mpsc::RecvTimeoutError::Disconnected) => return Err(mpsc::RecvError),
+    };
 
     match event_received {
         InputEvent::InputEvent(input_event) => match input_event {
-            Event::Key(key_event) => match key_event.code {
-                // Handle closing the application with the escape key
-                KeyCode::Esc => {
-                    terminal::disable_raw_mode().unwrap();
-
-                    crossterm::execute!(
-                        terminal.backend_mut(),
-                        terminal::LeaveAlternateScreen,
-                        event::DisableMouseCapture
-                    )
-                    .unwrap();
-
-                    terminal.show_cursor().unwrap();
-                    app.should_quit = true;
+            // Only handle key events if it is pressed down
+            Event::Key(key_event) => {
+                if key_event.kind == event::KeyEventKind::Press {
+                    app.handle_keyboard_event(key_event);
                 }
-
-                // handle all key events in app
-                _ => {
-                    // Only handle key events if it is pressed down
-                    if key_event.kind == event::KeyEventKind::Press {
-                        app.handle_keyboard_event(key_event);
-                    }
-                }
-            },
+            }
             Event::Mouse(_) => {}
             Event::Resize(_, _) => {}
             Event::FocusGained => {}
             Event::FocusLost => {}
-            Event::Paste(_) => {}
+            Event::Paste(text) => app.handle_paste(&text),
         },
         InputEvent::Tick => {
             app.on_tick();
         }
     }
+
+    Ok(())
 }
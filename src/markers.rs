@@ -0,0 +1,178 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::buffer::TextBuffer;
+use crate::history::{Edit, EditKind};
+
+/// The kinds of markers that can be shown in the gutter.
+/// Their order is the order they are drawn in when a line has several.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MarkerKind {
+    Modified,
+    Diagnostic,
+    Bookmark,
+}
+
+/// Markers attached to lines of a file.
+/// They move with their line when lines are inserted or removed above them.
+#[derive(Clone, Default, Debug)]
+pub struct Markers {
+    lines: BTreeMap<usize, BTreeSet<MarkerKind>>,
+}
+
+impl Markers {
+    pub fn new() -> Markers {
+        Markers::default()
+    }
+
+    pub fn add(&mut self, line: usize, kind: MarkerKind) {
+        self.lines.entry(line).or_default().insert(kind);
+    }
+
+    pub fn remove(&mut self, line: usize, kind: MarkerKind) {
+        if let Some(kinds) = self.lines.get_mut(&line) {
+            kinds.remove(&kind);
+            if kinds.is_empty() {
+                self.lines.remove(&line);
+            }
+        }
+    }
+
+    pub fn toggle(&mut self, line: usize, kind: MarkerKind) {
+        if self.has(line, kind) {
+            self.remove(line, kind);
+        } else {
+            self.add(line, kind);
+        }
+    }
+
+    pub fn has(&self, line: usize, kind: MarkerKind) -> bool {
+        self.lines
+            .get(&line)
+            .is_some_and(|kinds| kinds.contains(&kind))
+    }
+
+    // Removes every marker of one kind, e.g. the modified markers after a save.
+    pub fn clear(&mut self, kind: MarkerKind) {
+        for kinds in self.lines.values_mut() {
+            kinds.remove(&kind);
+        }
+        self.lines.retain(|_, kinds| !kinds.is_empty());
+    }
+
+    /// Moves the markers for an edit that is about to be applied to `buffer`,
+    /// and marks the edited lines as modified.
+    pub fn update_for_edit(&mut self, buffer: &TextBuffer, edit: &Edit) {
+        let line = buffer.char_to_line(edit.char_idx);
+        let breaks = line_breaks(&edit.text);
+
+        match edit.kind {
+            EditKind::Insert => {
+                self.shift(line + 1, breaks as isize);
+                for y in line..=line + breaks {
+                    self.add(y, MarkerKind::Modified);
+                }
+            }
+            EditKind::Remove => {
+                // Markers on removed lines are merged into the first line.
+                let removed: Vec<usize> = self
+                    .lines
+                    .range(line + 1..line + breaks + 1)
+                    .map(|(y, _)| *y)
+                    .collect();
+                for y in removed {
+                    let kinds = self.lines.remove(&y).unwrap_or_default();
+                    self.lines.entry(line).or_default().extend(kinds);
+                }
+
+                self.shift(line + breaks + 1, -(breaks as isize));
+                self.add(line, MarkerKind::Modified);
+            }
+        }
+    }
+
+    // Moves all markers from line `from` onwards by `delta` lines.
+    fn shift(&mut self, from: usize, delta: isize) {
+        if delta == 0 {
+            return;
+        }
+
+        let moved = self.lines.split_off(&from);
+        for (y, kinds) in moved {
+            let y = (y as isize + delta).max(0) as usize;
+            self.lines.entry(y).or_default().extend(kinds);
+        }
+    }
+}
+
+// Counts line breaks the same way the buffer does, "\r\n" being one break.
+fn line_breaks(text: &str) -> usize {
+    text.matches('\n').count() + text.matches('\r').count() - text.matches("\r\n").count()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The lines that have a marker of kind, in order.
+    fn lines_with(markers: &Markers, kind: MarkerKind) -> Vec<usize> {
+        markers
+            .lines
+            .iter()
+            .filter(|(_, kinds)| kinds.contains(&kind))
+            .map(|(y, _)| *y)
+            .collect()
+    }
+
+    fn bookmarked(lines: &[usize]) -> Markers {
+        let mut markers = Markers::new();
+        for line in lines {
+            markers.add(*line, MarkerKind::Bookmark);
+        }
+        markers
+    }
+
+    #[test]
+    fn removing_within_a_line_moves_nothing() {
+        let buffer = TextBuffer::from("one\ntwo\nthree\n");
+        let mut markers = bookmarked(&[0, 1, 2]);
+
+        // "tw" on line 1, with no line break in the removed text.
+        markers.update_for_edit(&buffer, &Edit::remove(&buffer, 4..6));
+
+        assert_eq!(lines_with(&markers, MarkerKind::Bookmark), [0, 1, 2]);
+        assert_eq!(lines_with(&markers, MarkerKind::Modified), [1]);
+    }
+
+    #[test]
+    fn removing_across_lines_merges_markers_into_the_first_line() {
+        let buffer = TextBuffer::from("one\ntwo\nthree\nfour\nfive\n");
+        let mut markers = bookmarked(&[2, 3, 4]);
+        markers.add(1, MarkerKind::Diagnostic);
+
+        // From the end of "one" to the start of "four", which joins line 0.
+        markers.update_for_edit(&buffer, &Edit::remove(&buffer, 3..14));
+
+        assert_eq!(lines_with(&markers, MarkerKind::Bookmark), [0, 1]);
+        assert_eq!(lines_with(&markers, MarkerKind::Diagnostic), [0]);
+        assert_eq!(lines_with(&markers, MarkerKind::Modified), [0]);
+    }
+
+    #[test]
+    fn inserting_line_breaks_moves_markers_down() {
+        let buffer = TextBuffer::from("one\ntwo\nthree\n");
+        let mut markers = bookmarked(&[0, 1, 2]);
+
+        // Two new lines in the middle of "two".
+        markers.update_for_edit(&buffer, &Edit::insert(5, "a\r\nb\n"));
+
+        assert_eq!(lines_with(&markers, MarkerKind::Bookmark), [0, 1, 4]);
+        assert_eq!(lines_with(&markers, MarkerKind::Modified), [1, 2, 3]);
+    }
+
+    #[test]
+    fn counts_line_breaks_like_the_buffer() {
+        assert_eq!(line_breaks("a\r\nb\nc\rd"), 3);
+        assert_eq!(line_breaks("\r\r\n\n"), 3);
+        assert_eq!(line_breaks("abc"), 0);
+    }
+}
@@ -0,0 +1,399 @@
+use std::fs::{File, Metadata, OpenOptions};
+use std::io::Write;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use strum_macros::{Display, EnumString};
+
+use crate::error::Error;
+use crate::history_store;
+
+// Files are saved by writing a temporary file next to them and renaming it over
+// the file, so a crash or a full disk halfway leaves the old file whole. The
+// temporary file gets the permissions and owner of the file, a symlink is saved
+// through to the file it points at, and both the file and its directory are
+// synced before the save counts as done.
+
+/// Whether the previous version of a file is kept when it is saved.
+#[derive(Clone, Copy, Debug, Default, Display, EnumString, PartialEq, Eq)]
+#[strum(serialize_all = "lowercase")]
+pub enum BackupMode {
+    #[default]
+    None,
+    /// The previous version is kept next to the file as "file~".
+    Tilde,
+    /// Every previous version is kept in the backup directory, named after
+    /// the path of the file and the time it was replaced.
+    Dir,
+}
+
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Backup {
+    pub mode: BackupMode,
+    // The directory of BackupMode::Dir. The state directory is used when unset.
+    pub dir: Option<PathBuf>,
+}
+
+impl Backup {
+    // Copies the file as it is on disk before it is replaced.
+    fn write(&self, path: &Path) -> Result<(), Error> {
+        let backup_path = match self.mode {
+            BackupMode::None => return Ok(()),
+            BackupMode::Tilde => {
+                let mut backup_path = path.as_os_str().to_os_string();
+                backup_path.push("~");
+                PathBuf::from(backup_path)
+            }
+            BackupMode::Dir => {
+                let dir = match &self.dir {
+                    Some(dir) => dir.clone(),
+                    None => history_store::rustex_state_dir()
+                        .ok_or(Error::NoPath)?
+                        .join("backup"),
+                };
+                std::fs::create_dir_all(&dir).map_err(|e| Error::io(&dir, e))?;
+
+                // "/home/user/notes.txt" becomes "%home%user%notes.txt.20240131-235959".
+                let name = path
+                    .to_string_lossy()
+                    .replace(std::path::MAIN_SEPARATOR, "%");
+                let backup_path = dir.join(format!("{}.{}", name, timestamp(SystemTime::now())));
+                return copy_new(path, &backup_path);
+            }
+        };
+
+        std::fs::copy(path, &backup_path)
+            .map(|_| ())
+            .map_err(|e| Error::io(&backup_path, e))
+    }
+}
+
+// Copies path to a file that does not exist yet: backup_path, or "backup_path.1"
+// and so on when the file was saved more than once within the same second.
+fn copy_new(path: &Path, backup_path: &Path) -> Result<(), Error> {
+    for n in 0..100 {
+        let mut candidate = backup_path.as_os_str().to_os_string();
+        if n > 0 {
+            candidate.push(format!(".{}", n));
+        }
+        let candidate = PathBuf::from(candidate);
+
+        let mut backup = match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(backup) => backup,
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(Error::io(&candidate, e)),
+        };
+
+        // The backup gets the permissions of the file, as a copy would.
+        let mut file = File::open(path).map_err(|e| Error::io(path, e))?;
+        return std::io::copy(&mut file, &mut backup)
+            .and_then(|_| backup.set_permissions(file.metadata()?.permissions()))
+            .map_err(|e| Error::io(&candidate, e));
+    }
+
+    Err(Error::io(
+        backup_path,
+        std::io::Error::new(std::io::ErrorKind::AlreadyExists, "too many backups"),
+    ))
+}
+
+/// Saves the bytes of a file to path, replacing the file atomically where possible.
+pub fn write_file(path: &Path, contents: &[u8], backup: &Backup) -> Result<(), Error> {
+    let path = resolve_symlinks(path);
+
+    let metadata = match std::fs::metadata(&path) {
+        Ok(metadata) => Some(metadata),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => None,
+        Err(e) => return Err(Error::io(&path, e)),
+    };
+
+    if metadata.is_some() {
+        // The rename would replace a read-only file just as well,
+        // so it is checked first that the file itself may be written.
+        OpenOptions::new()
+            .write(true)
+            .open(&path)
+            .map_err(|e| Error::io(&path, e))?;
+        backup.write(&path)?;
+    }
+
+    match replace(&path, contents, metadata.as_ref()) {
+        Ok(true) => Ok(()),
+        Ok(false) => write_in_place(&path, contents).map_err(|e| Error::io(&path, e)),
+        Err(e) => Err(Error::io(&path, e)),
+    }
+}
+
+// Writes a temporary file and renames it over path.
+// Returns false, having changed nothing, when the file cannot be replaced
+// without changing it in other ways than its contents.
+fn replace(
+    path: &Path,
+    contents: &[u8],
+    metadata: Option<&Metadata>,
+) -> Result<bool, std::io::Error> {
+    // Other names for the file would keep pointing at the old contents.
+    if metadata.is_some_and(|metadata| metadata.nlink() > 1) {
+        return Ok(false);
+    }
+
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let mut temp_name = std::ffi::OsString::from(".");
+    temp_name.push(path.file_name().unwrap_or_default());
+    temp_name.push(format!(".{}.rustex-tmp", std::process::id()));
+    let temp_path = dir.join(temp_name);
+
+    // A directory that cannot be written to still allows writing the file in place.
+    let temp = match OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&temp_path)
+    {
+        Ok(temp) => temp,
+        Err(e) if e.kind() == std::io::ErrorKind::PermissionDenied => return Ok(false),
+        Err(e) => return Err(e),
+    };
+
+    let result = write_temp(temp, contents, metadata).and_then(|kept| match kept {
+        true => std::fs::rename(&temp_path, path).map(|()| true),
+        false => Ok(false),
+    });
+
+    match result {
+        Ok(true) => {
+            // The rename is only durable once the directory is synced.
+            // Some file systems do not sync directories, which is not an error.
+            if let Ok(dir) = File::open(dir) {
+                let _ = dir.sync_all();
+            }
+            Ok(true)
+        }
+        _ => {
+            let _ = std::fs::remove_file(&temp_path);
+            result
+        }
+    }
+}
+
+// Writes and syncs the temporary file, with the permissions and owner of the
+// file it replaces. Returns false when the owner cannot be kept.
+fn write_temp(
+    mut temp: File,
+    contents: &[u8],
+    metadata: Option<&Metadata>,
+) -> Result<bool, std::io::Error> {
+    if let Some(metadata) = metadata {
+        let temp_metadata = temp.metadata()?;
+        if (temp_metadata.uid(), temp_metadata.gid()) != (metadata.uid(), metadata.gid())
+            && std::os::unix::fs::fchown(&temp, Some(metadata.uid()), Some(metadata.gid())).is_err()
+        {
+            return Ok(false);
+        }
+        temp.set_permissions(metadata.permissions())?;
+    }
+
+    temp.write_all(contents)?;
+    temp.sync_all()?;
+    Ok(true)
+}
+
+// Truncates the file and writes it again. This keeps everything about the file
+// but its contents, but a crash halfway leaves it cut short.
+fn write_in_place(path: &Path, contents: &[u8]) -> Result<(), std::io::Error> {
+    let mut file = File::create(path)?;
+    file.write_all(contents)?;
+    file.sync_all()
+}
+
+// The file a path ends up at after following symlinks, so the symlink stays
+// and the file it points at is saved. A link to a file that does not exist
+// yet is followed as far as it goes.
+fn resolve_symlinks(path: &Path) -> PathBuf {
+    let mut path = path.to_path_buf();
+
+    // Bounded, as links can point at each other in a loop.
+    for _ in 0..40 {
+        let target = match std::fs::symlink_metadata(&path) {
+            Ok(metadata) if metadata.file_type().is_symlink() => match std::fs::read_link(&path) {
+                Ok(target) => target,
+                Err(_) => break,
+            },
+            _ => break,
+        };
+
+        path = match path.parent() {
+            Some(dir) => dir.join(target),
+            None => target,
+        };
+    }
+
+    path
+}
+
+// A UTC time as "20240131-235959".
+fn timestamp(time: SystemTime) -> String {
+    let secs = time
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs());
+    let (days, secs) = (secs / 86400, secs % 86400);
+
+    // Converts days since 1970-01-01 to a date, from Howard Hinnant's
+    // civil_from_days, in eras of 400 years that start on March 1st.
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let day_of_era = z.rem_euclid(146097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + (month <= 2) as i64;
+
+    format!(
+        "{:04}{:02}{:02}-{:02}{:02}{:02}",
+        year,
+        month,
+        day,
+        secs / 3600,
+        secs / 60 % 60,
+        secs % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::{symlink, PermissionsExt};
+    use std::time::Duration;
+
+    // An empty directory of its own for each test.
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustex-save-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn follows_relative_and_absolute_symlinks() {
+        let dir = test_dir("symlinks");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        std::fs::write(dir.join("sub/file.txt"), "text").unwrap();
+        symlink("sub/file.txt", dir.join("relative")).unwrap();
+        symlink(dir.join("relative"), dir.join("absolute")).unwrap();
+
+        assert_eq!(
+            resolve_symlinks(&dir.join("relative")),
+            dir.join("sub/file.txt")
+        );
+        assert_eq!(
+            resolve_symlinks(&dir.join("absolute")),
+            dir.join("sub/file.txt")
+        );
+        assert_eq!(
+            resolve_symlinks(&dir.join("sub/file.txt")),
+            dir.join("sub/file.txt")
+        );
+
+        // A link to a file that does not exist yet points where it will be created.
+        symlink("new.txt", dir.join("dangling")).unwrap();
+        assert_eq!(resolve_symlinks(&dir.join("dangling")), dir.join("new.txt"));
+    }
+
+    #[test]
+    fn stops_at_looping_symlinks() {
+        let dir = test_dir("loop");
+        symlink("b", dir.join("a")).unwrap();
+        symlink("a", dir.join("b")).unwrap();
+
+        let resolved = resolve_symlinks(&dir.join("a"));
+        assert!(resolved == dir.join("a") || resolved == dir.join("b"));
+    }
+
+    #[test]
+    fn replaces_the_file_keeping_its_mode() {
+        let dir = test_dir("mode");
+        let path = dir.join("script.sh");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o750)).unwrap();
+
+        write_file(&path, b"new", &Backup::default()).unwrap();
+
+        assert_eq!(std::fs::read(&path).unwrap(), b"new");
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o750);
+        // No temporary file is left behind.
+        assert_eq!(std::fs::read_dir(&dir).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn writes_hard_linked_files_in_place() {
+        let dir = test_dir("hardlink");
+        let path = dir.join("file.txt");
+        std::fs::write(&path, "old").unwrap();
+        std::fs::hard_link(&path, dir.join("other.txt")).unwrap();
+        let inode = std::fs::metadata(&path).unwrap().ino();
+
+        write_file(&path, b"new", &Backup::default()).unwrap();
+
+        assert_eq!(std::fs::metadata(&path).unwrap().ino(), inode);
+        assert_eq!(std::fs::read(dir.join("other.txt")).unwrap(), b"new");
+    }
+
+    #[test]
+    fn saves_through_symlinks() {
+        let dir = test_dir("through");
+        std::fs::write(dir.join("file.txt"), "old").unwrap();
+        symlink("file.txt", dir.join("link")).unwrap();
+
+        write_file(&dir.join("link"), b"new", &Backup::default()).unwrap();
+
+        assert!(std::fs::symlink_metadata(dir.join("link"))
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(std::fs::read(dir.join("file.txt")).unwrap(), b"new");
+    }
+
+    #[test]
+    fn backups_in_the_same_second_get_a_suffix() {
+        let dir = test_dir("backup");
+        let path = dir.join("file.txt");
+        let backup_path = dir.join("file.txt.20240131-235959");
+
+        for version in ["one", "two", "three"] {
+            std::fs::write(&path, version).unwrap();
+            copy_new(&path, &backup_path).unwrap();
+        }
+
+        let read = |name: &str| std::fs::read_to_string(dir.join(name)).unwrap();
+        assert_eq!(read("file.txt.20240131-235959"), "one");
+        assert_eq!(read("file.txt.20240131-235959.1"), "two");
+        assert_eq!(read("file.txt.20240131-235959.2"), "three");
+    }
+
+    #[test]
+    fn timestamps_known_dates() {
+        let at = |secs| timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(secs));
+
+        assert_eq!(at(0), "19700101-000000");
+        assert_eq!(at(946684799), "19991231-235959");
+        assert_eq!(at(951827696), "20000229-123456");
+        assert_eq!(at(1706745599), "20240131-235959");
+        // 2100 is not a leap year.
+        assert_eq!(at(4107542400), "21000301-000000");
+    }
+}
@@ -0,0 +1,360 @@
+use std::cell::{Cell, RefCell};
+use std::convert::TryFrom;
+use std::io::{BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::buffer::TextBuffer;
+use crate::history_store;
+
+// Swap files keep the unsaved text of modified files, so it can be recovered
+// when the editor dies before the files are saved. Each file gets one swap file
+// in the state directory, named after a hash of its canonical path like the
+// history files. The swap file starts with the pid of the editor that writes it,
+// so a second editor opening the same file can tell that it is in use.
+//
+// Unnamed files, such as text read from stdin, have no path to name the swap
+// file after. They get a key of their own, and when the editor starts, the swap
+// files of unnamed files whose editor is gone are offered for recovery.
+
+const HEADER: &str = "rustex-swap 1";
+
+/// The contents of a swap file found on disk.
+#[derive(Debug, Clone)]
+pub struct Swap {
+    pub key: String,
+    pub pid: u32,
+    pub modified: SystemTime,
+    pub text: String,
+}
+
+impl Swap {
+    /// Whether another editor that is still running writes this swap file.
+    pub fn is_owned_by_other(&self) -> bool {
+        self.pid != std::process::id() && process_is_running(self.pid)
+    }
+
+    /// The name of the unnamed file this swap file is for, None for a file with a path.
+    pub fn unnamed_name(&self) -> Option<&str> {
+        let (name, _) = self.key.strip_suffix(')')?.rsplit_once(" (unnamed ")?;
+        Some(name)
+    }
+}
+
+// $XDG_STATE_HOME/rustex/swap, next to the history directory.
+pub fn swap_dir() -> Option<PathBuf> {
+    Some(history_store::rustex_state_dir()?.join("swap"))
+}
+
+// The canonical path of a file that may not exist yet, as new files
+// have swap files too before they are first saved.
+fn canonical(path: &Path) -> Option<String> {
+    let canonical = match path.canonicalize() {
+        Ok(canonical) => canonical,
+        Err(_) => {
+            let parent = match path.parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => parent,
+                _ => Path::new("."),
+            };
+            parent.canonicalize().ok()?.join(path.file_name()?)
+        }
+    };
+
+    Some(canonical.to_string_lossy().to_string())
+}
+
+/// The key the swap file of a file is named after: its canonical path.
+pub fn key(path: &Path) -> Option<String> {
+    canonical(path)
+}
+
+fn swap_path(key: &str) -> Option<PathBuf> {
+    let name = format!("{}.swp", history_store::path_hash(key));
+    Some(swap_dir()?.join(name))
+}
+
+fn write(key: &str, buffer: &TextBuffer) -> Result<(), std::io::Error> {
+    let swap_path = swap_path(key)
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::NotFound, "no state directory"))?;
+    std::fs::create_dir_all(swap_path.parent().unwrap())?;
+    write_at(&swap_path, key, buffer)
+}
+
+// Writes the text of a file to its swap file.
+// It goes to a temporary file first, so a crash while writing leaves the old swap file.
+fn write_at(swap_path: &Path, key: &str, buffer: &TextBuffer) -> Result<(), std::io::Error> {
+    let temp_path = swap_path.with_extension("swp.tmp");
+    let mut writer = BufWriter::new(std::fs::File::create(&temp_path)?);
+    writeln!(writer, "{}", HEADER)?;
+    writeln!(writer, "{}", std::process::id())?;
+    writeln!(writer, "{}", key)?;
+    buffer.write_to(&mut writer)?;
+    writer.flush()?;
+    drop(writer);
+
+    std::fs::rename(temp_path, swap_path)
+}
+
+/// Reads the swap file of a file.
+/// Returns None when there is none, or when it cannot be read.
+pub fn read(path: &Path) -> Option<Swap> {
+    let key = key(path)?;
+    read_at(&swap_path(&key)?).filter(|swap| swap.key == key)
+}
+
+/// The swap files of unnamed files left behind by editors that are no longer running.
+pub fn orphaned_unnamed() -> Vec<Swap> {
+    let entries = match swap_dir().map(std::fs::read_dir) {
+        Some(Ok(entries)) => entries,
+        _ => return Vec::new(),
+    };
+
+    let mut swaps: Vec<Swap> = entries
+        .filter_map(|entry| entry.ok())
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "swp"))
+        .filter_map(|entry| read_at(&entry.path()))
+        .filter(|swap| swap.unnamed_name().is_some() && !process_is_running(swap.pid))
+        .collect();
+    swaps.sort_by_key(|swap| swap.modified);
+    swaps
+}
+
+fn read_at(swap_path: &Path) -> Option<Swap> {
+    let file = std::fs::File::open(swap_path).ok()?;
+    let modified = file.metadata().and_then(|m| m.modified()).ok()?;
+    let mut reader = BufReader::new(file);
+
+    if read_line(&mut reader)? != HEADER {
+        return None;
+    }
+    let pid = read_line(&mut reader)?.parse().ok()?;
+    let key = read_line(&mut reader)?;
+
+    let mut text = String::new();
+    reader.read_to_string(&mut text).ok()?;
+
+    Some(Swap {
+        key,
+        pid,
+        modified,
+        text,
+    })
+}
+
+/// Removes the swap file of a file, if there is one.
+pub fn remove(path: &Path) {
+    if let Some(key) = key(path) {
+        remove_key(&key);
+    }
+}
+
+pub fn remove_key(key: &str) {
+    if let Some(swap_path) = swap_path(key) {
+        let _ = std::fs::remove_file(swap_path);
+    }
+}
+
+#[derive(Debug)]
+enum Job {
+    Write { key: String, buffer: TextBuffer },
+    Remove { key: String },
+    Flush(Sender<()>),
+}
+
+impl Job {
+    fn key(&self) -> Option<&str> {
+        match self {
+            Job::Write { key, .. } | Job::Remove { key } => Some(key),
+            Job::Flush(_) => None,
+        }
+    }
+}
+
+/// SwapWriter writes and removes swap files on a thread of its own, so writing
+/// the swap file of a large file does not hold up editing. The jobs are done
+/// in the order they are sent, and the thread starts with the first one.
+#[derive(Debug, Default)]
+pub struct SwapWriter {
+    jobs: RefCell<Option<Sender<Job>>>,
+    unnamed_count: Cell<usize>,
+}
+
+impl SwapWriter {
+    // Cloning the buffer is cheap, the rope shares its chunks with the copy.
+    pub fn write(&self, key: &str, buffer: &TextBuffer) {
+        self.send(Job::Write {
+            key: key.to_string(),
+            buffer: buffer.clone(),
+        });
+    }
+
+    pub fn remove(&self, key: &str) {
+        self.send(Job::Remove {
+            key: key.to_string(),
+        });
+    }
+
+    /// Waits until the jobs sent so far are done, before the editor exits.
+    pub fn flush(&self) {
+        if self.jobs.borrow().is_none() {
+            return;
+        }
+        let (done, wait) = channel();
+        self.send(Job::Flush(done));
+        let _ = wait.recv();
+    }
+
+    /// A new key for the swap file of an unnamed file. The pid and the time
+    /// keep it apart from the keys of other editors, running or not.
+    pub fn unnamed_key(&self, name: &str) -> String {
+        let count = self.unnamed_count.get() + 1;
+        self.unnamed_count.set(count);
+        let time = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |time| time.as_nanos());
+
+        format!(
+            "{} (unnamed {} {} {})",
+            name,
+            std::process::id(),
+            time,
+            count
+        )
+    }
+
+    fn send(&self, job: Job) {
+        let mut jobs = self.jobs.borrow_mut();
+        let sender = jobs.get_or_insert_with(|| {
+            let (sender, receiver) = channel();
+            std::thread::spawn(move || run_jobs(receiver));
+            sender
+        });
+        let _ = sender.send(job);
+    }
+}
+
+// A write is skipped when a later job waiting for the same swap file
+// replaces it, so a slow write does not leave a queue of them behind.
+fn run_jobs(jobs: Receiver<Job>) {
+    while let Ok(job) = jobs.recv() {
+        let mut pending = vec![job];
+        pending.extend(jobs.try_iter());
+
+        for (index, job) in pending.iter().enumerate() {
+            let replaced = || {
+                pending[index + 1..]
+                    .iter()
+                    .any(|later| later.key() == job.key())
+            };
+            match job {
+                // A failed write is not retried, the next change writes it again.
+                Job::Write { key, buffer } if !replaced() => {
+                    let _ = write(key, buffer);
+                }
+                Job::Write { .. } => {}
+                Job::Remove { key } => remove_key(key),
+                Job::Flush(done) => {
+                    let _ = done.send(());
+                }
+            }
+        }
+    }
+}
+
+// Signal 0 only checks that the process exists. EPERM means it exists
+// but belongs to another user.
+fn process_is_running(pid: u32) -> bool {
+    let pid = match libc::pid_t::try_from(pid) {
+        Ok(pid) if pid > 0 => pid,
+        _ => return false,
+    };
+
+    let result = unsafe { libc::kill(pid, 0) };
+    result == 0 || std::io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+fn read_line<R: BufRead>(reader: &mut R) -> Option<String> {
+    let mut line = String::new();
+    if reader.read_line(&mut line).ok()? == 0 {
+        return None;
+    }
+
+    Some(line.trim_end_matches('\n').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("rustex-swap-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn round_trips_the_text() {
+        let dir = test_dir("round-trip");
+        let swap_path = dir.join("file.swp");
+        let text = "first line\r\nsécond\n\nlast";
+
+        write_at(&swap_path, "/notes.txt", &TextBuffer::from(text)).unwrap();
+        let swap = read_at(&swap_path).unwrap();
+
+        assert_eq!(swap.key, "/notes.txt");
+        assert_eq!(swap.pid, std::process::id());
+        assert_eq!(swap.text, text);
+        assert!(!swap.is_owned_by_other());
+        assert!(!dir.join("file.swp.tmp").exists());
+    }
+
+    #[test]
+    fn ignores_other_files() {
+        let dir = test_dir("other");
+        let swap_path = dir.join("file.swp");
+        std::fs::write(&swap_path, "not a swap file\n1\n/notes.txt\ntext").unwrap();
+
+        assert!(read_at(&swap_path).is_none());
+        assert!(read_at(&dir.join("missing.swp")).is_none());
+    }
+
+    #[test]
+    fn tells_unnamed_keys_apart() {
+        let writer = SwapWriter::default();
+        let swap = |key: String| Swap {
+            key,
+            pid: 1,
+            modified: SystemTime::now(),
+            text: String::new(),
+        };
+
+        let first = writer.unnamed_key("stdin (copy)");
+        assert_ne!(first, writer.unnamed_key("stdin (copy)"));
+        assert_eq!(swap(first).unnamed_name(), Some("stdin (copy)"));
+        assert_eq!(swap("/notes.txt".to_string()).unnamed_name(), None);
+    }
+
+    #[test]
+    fn owned_by_other_while_the_writer_runs() {
+        let mut child = std::process::Command::new("sleep")
+            .arg("10")
+            .spawn()
+            .unwrap();
+        let swap = Swap {
+            key: "/notes.txt".to_string(),
+            pid: child.id(),
+            modified: SystemTime::now(),
+            text: String::new(),
+        };
+        assert!(swap.is_owned_by_other());
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+        assert!(!swap.is_owned_by_other());
+
+        let nobody = Swap { pid: 0, ..swap };
+        assert!(!nobody.is_owned_by_other());
+    }
+}
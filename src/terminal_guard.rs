@@ -0,0 +1,74 @@
+use std::io::{IsTerminal, Write};
+
+use crossterm::{cursor, event, terminal};
+
+/// The terminal the editor draws on: stdout, or the controlling terminal
+/// when stdout is piped, so the output of the editor can go elsewhere.
+pub fn output() -> Result<Box<dyn Write + Send>, std::io::Error> {
+    if std::io::stdout().is_terminal() {
+        Ok(Box::new(std::io::stdout()))
+    } else {
+        Ok(Box::new(
+            std::fs::OpenOptions::new().write(true).open("/dev/tty")?,
+        ))
+    }
+}
+
+/// TerminalGuard puts the terminal in the state the editor needs,
+/// and puts it back when it is dropped, however the editor exits.
+pub struct TerminalGuard {
+    _private: (),
+}
+
+impl TerminalGuard {
+    pub fn enter() -> Result<TerminalGuard, std::io::Error> {
+        // The guard exists before anything is changed, so a failure
+        // half way still undoes what was done.
+        let guard = TerminalGuard { _private: () };
+
+        terminal::enable_raw_mode()?;
+        crossterm::execute!(
+            output()?,
+            terminal::EnterAlternateScreen,
+            event::EnableMouseCapture,
+            event::EnableBracketedPaste
+        )?;
+
+        Ok(guard)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let _ = restore();
+    }
+}
+
+/// Disables raw mode and leaves the alternate screen.
+/// It is safe to call more than once, e.g. from the panic hook and then the guard.
+pub fn restore() -> Result<(), std::io::Error> {
+    // Raw mode is disabled first, as the terminal may be gone,
+    // and then writing to it fails.
+    let raw_mode = terminal::disable_raw_mode();
+
+    crossterm::execute!(
+        output()?,
+        event::DisableBracketedPaste,
+        event::DisableMouseCapture,
+        terminal::LeaveAlternateScreen,
+        cursor::Show
+    )?;
+
+    raw_mode
+}
+
+/// Restores the terminal before a panic message is printed,
+/// so the message is readable and the shell is usable afterwards.
+pub fn install_panic_hook() {
+    let default_hook = std::panic::take_hook();
+
+    std::panic::set_hook(Box::new(move |info| {
+        let _ = restore();
+        default_hook(info);
+    }));
+}
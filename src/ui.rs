@@ -1,6 +1,11 @@
 use crate::{
-    app::{App, WindowState},
-    widgets,
+    app::{App, LineNumbers, StatusItem, WindowState},
+    markers::MarkerKind,
+    widgets::{
+        self,
+        diff_dialog::{DialogChoice, DiffDialog},
+        navigation::NavigationFocus,
+    },
 };
 use strum::*;
 
@@ -8,7 +13,7 @@ use tui::{
     backend::Backend,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
-    text::{Span, Spans, Text},
+    text::{Span, Spans},
     widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
     Frame,
 };
@@ -19,6 +24,7 @@ pub fn draw<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
             [
                 Constraint::Length(2),
                 Constraint::Min(4),
+                Constraint::Length(1), // Status bar
                 Constraint::Length(8), // Console/Informational
             ]
             .as_ref(),
@@ -27,7 +33,360 @@ pub fn draw<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
 
     draw_header(frame, app, chunks[0]);
     draw_viewport(frame, app, chunks[1]);
-    draw_console(frame, chunks[2]);
+    draw_status_bar(frame, app, chunks[2]);
+    draw_console(frame, chunks[3]);
+
+    // Dialogs are drawn last, on top of everything else.
+    draw_finder(frame, app);
+    draw_confirm_dialog(frame, app);
+    draw_recover_dialog(frame, app);
+    draw_changed_dialog(frame, app);
+}
+
+// Returns a rect of the given size centered in area, shrunk to fit if needed.
+fn centered_rect(width: u16, height: u16, area: Rect) -> Rect {
+    let width = width.min(area.width);
+    let height = height.min(area.height);
+
+    Rect::new(
+        area.x + (area.width - width) / 2,
+        area.y + (area.height - height) / 2,
+        width,
+        height,
+    )
+}
+
+fn draw_finder<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
+    let finder = match &app.context.finder_window {
+        Some(finder) => finder,
+        None => return,
+    };
+
+    let size = frame.size();
+    let area = centered_rect(size.width * 4 / 5, size.height * 4 / 5, size);
+    let title_style = Style::default()
+        .fg(Color::Magenta)
+        .add_modifier(Modifier::BOLD);
+
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(Span::styled("Find File", title_style));
+    let inner = block.inner(area);
+    frame.render_widget(tui::widgets::Clear, area);
+    frame.render_widget(block, area);
+
+    let rows = Layout::default()
+        .constraints([Constraint::Length(2), Constraint::Min(1)])
+        .split(inner);
+    let columns = Layout::default()
+        .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+        .direction(Direction::Horizontal)
+        .split(rows[1]);
+
+    let query = Spans::from(vec![
+        Span::styled("> ", title_style),
+        Span::raw(finder.query.clone()),
+        Span::styled(" ", Style::default().add_modifier(Modifier::REVERSED)),
+        Span::styled(
+            format!("  {} files", finder.matches.len()),
+            Style::default().fg(Color::DarkGray),
+        ),
+    ]);
+    frame.render_widget(
+        Paragraph::new(query).block(Block::default().borders(Borders::BOTTOM)),
+        rows[0],
+    );
+
+    // Only the matches that fit are drawn, scrolled so the selected one is
+    // on the last row once it is past the first page, as the list would.
+    let height = columns[0].height as usize;
+    let first = (finder.selected + 1).saturating_sub(height.max(1));
+
+    // The matched chars of each path are highlighted.
+    let items: Vec<ListItem> = finder
+        .matches
+        .iter()
+        .skip(first)
+        .take(height)
+        .map(|m| {
+            let spans: Vec<Span> = m
+                .path
+                .to_string_lossy()
+                .chars()
+                .enumerate()
+                .map(|(i, c)| match m.positions.contains(&i) {
+                    true => Span::styled(c.to_string(), Style::default().fg(Color::Yellow)),
+                    false => Span::raw(c.to_string()),
+                })
+                .collect();
+            ListItem::new(Spans::from(spans))
+        })
+        .collect();
+
+    let mut list_state = tui::widgets::ListState::default();
+    list_state.select(Some(finder.selected - first).filter(|_| !finder.matches.is_empty()));
+
+    let list = List::new(items)
+        .block(Block::default().borders(Borders::RIGHT))
+        .highlight_style(Style::default().add_modifier(Modifier::BOLD | Modifier::REVERSED));
+    frame.render_stateful_widget(list, columns[0], &mut list_state);
+
+    let preview: Vec<Spans> = finder
+        .preview
+        .iter()
+        .map(|line| Spans::from(line.replace('\t', "    ")))
+        .collect();
+    let preview = Paragraph::new(preview);
+    frame.render_widget(preview, columns[1]);
+}
+
+fn draw_confirm_dialog<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
+    let confirm = match &app.context.confirm_window {
+        Some(confirm) => confirm,
+        None => return,
+    };
+
+    let buttons = dialog_buttons(widgets::confirm::ConfirmChoice::iter(), confirm.selected);
+
+    let mut text = vec![Spans::from(confirm.message.clone()), Spans::from("")];
+    if let Some(error) = &confirm.error {
+        text.push(Spans::from(Span::styled(
+            error.clone(),
+            Style::default().fg(Color::Red),
+        )));
+    }
+    text.push(Spans::from(buttons));
+
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        "Unsaved Changes",
+        Style::default()
+            .fg(Color::Magenta)
+            .add_modifier(Modifier::BOLD),
+    ));
+
+    let area = centered_rect(60, text.len() as u16 + 2, frame.size());
+    let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+
+    frame.render_widget(tui::widgets::Clear, area);
+    frame.render_widget(paragraph, area);
+}
+
+// The choices of a dialog in a row, with the selected one highlighted.
+fn dialog_buttons<T: std::fmt::Display>(
+    choices: impl Iterator<Item = T>,
+    selected: usize,
+) -> Vec<Span<'static>> {
+    choices
+        .enumerate()
+        .flat_map(|(i, choice)| {
+            let style = if i == selected {
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::Magenta)
+            };
+            vec![
+                Span::styled(format!(" {} ", choice), style),
+                Span::raw("  "),
+            ]
+        })
+        .collect()
+}
+
+fn draw_recover_dialog<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
+    if let Some(recover) = &app.context.recover_window {
+        draw_diff_dialog(frame, "Recover Unsaved Changes", recover);
+    }
+}
+
+fn draw_changed_dialog<B: Backend>(frame: &mut Frame<B>, app: &mut App) {
+    if let Some(changed) = &app.context.changed_window {
+        draw_diff_dialog(frame, "File Changed On Disk", changed);
+    }
+}
+
+// The dialog grows to show the diff once it is asked for.
+fn draw_diff_dialog<B: Backend, C: DialogChoice, T>(
+    frame: &mut Frame<B>,
+    title: &str,
+    dialog: &DiffDialog<C, T>,
+) {
+    let buttons = dialog_buttons(C::iter(), dialog.selected);
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        title.to_string(),
+        Style::default()
+            .fg(Color::Magenta)
+            .add_modifier(Modifier::BOLD),
+    ));
+
+    let diff = match &dialog.diff {
+        Some(diff) => diff,
+        None => {
+            let text = vec![
+                Spans::from(dialog.message.clone()),
+                Spans::from(""),
+                Spans::from(buttons),
+            ];
+            let area = centered_rect(60, 8, frame.size());
+            let paragraph = Paragraph::new(text).block(block).wrap(Wrap { trim: true });
+
+            frame.render_widget(tui::widgets::Clear, area);
+            frame.render_widget(paragraph, area);
+            return;
+        }
+    };
+
+    let size = frame.size();
+    let area = centered_rect(size.width * 4 / 5, size.height * 4 / 5, size);
+    let inner = block.inner(area);
+    frame.render_widget(tui::widgets::Clear, area);
+    frame.render_widget(block, area);
+
+    let rows = Layout::default()
+        .constraints([Constraint::Min(1), Constraint::Length(2)])
+        .split(inner);
+
+    let lines: Vec<Spans> = diff
+        .iter()
+        .skip(dialog.scroll)
+        .map(|line| {
+            let style = match line.chars().next() {
+                Some('@') => Style::default().fg(Color::Cyan),
+                _ if line.starts_with("---") || line.starts_with("+++") => {
+                    Style::default().add_modifier(Modifier::BOLD)
+                }
+                Some('-') => Style::default().fg(Color::Red),
+                Some('+') => Style::default().fg(Color::Green),
+                _ => Style::default(),
+            };
+            Spans::from(Span::styled(line.replace('\t', "    "), style))
+        })
+        .collect();
+    frame.render_widget(Paragraph::new(lines), rows[0]);
+
+    frame.render_widget(
+        Paragraph::new(Spans::from(buttons)).block(Block::default().borders(Borders::TOP)),
+        rows[1],
+    );
+}
+
+// An open prompt takes the place of the status bar.
+fn draw_prompt<B: Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
+    let prompt = match &app.context.prompt_window {
+        Some(prompt) => prompt,
+        None => return,
+    };
+
+    let mut spans = vec![
+        Span::styled(
+            format!(" {}: ", prompt.label),
+            Style::default()
+                .fg(Color::Black)
+                .bg(Color::Magenta)
+                .add_modifier(Modifier::BOLD),
+        ),
+        Span::raw(" "),
+    ];
+
+    // The char under the cursor is drawn reversed, or a space past the end.
+    let mut chars = prompt.input.chars();
+    let before: String = chars.by_ref().take(prompt.cursor).collect();
+    let under = chars.next().map_or(" ".to_string(), |c| c.to_string());
+    let after: String = chars.collect();
+
+    spans.push(Span::raw(before));
+    spans.push(Span::styled(
+        under,
+        Style::default().add_modifier(Modifier::REVERSED),
+    ));
+    spans.push(Span::raw(after));
+
+    let paragraph = Paragraph::new(Spans::from(spans))
+        .style(Style::default().bg(Color::Black).fg(Color::White));
+    frame.render_widget(paragraph, area);
+}
+
+fn draw_status_bar<B: Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
+    if app.context.prompt_window.is_some() {
+        draw_prompt(frame, app, area);
+        return;
+    }
+
+    let mode = app.context.current_window.to_string().to_uppercase();
+    let items = app.context.status_bar.items.clone();
+    let file = app.context.file_manager_window.current_file();
+
+    let separator = Span::styled(" │ ", Style::default().fg(Color::DarkGray));
+    let mut spans = Vec::new();
+
+    for item in items {
+        let span = match item {
+            StatusItem::Mode => Span::styled(
+                format!(" {} ", mode),
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::Magenta)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            StatusItem::File => match &file.path {
+                Some(path) => Span::raw(path.display().to_string()),
+                None => Span::raw(file.name.to_string_lossy().to_string()),
+            },
+            StatusItem::Modified if file.deleted => {
+                Span::styled("[deleted]", Style::default().fg(Color::Red))
+            }
+            StatusItem::Modified if file.stale => {
+                Span::styled("[stale]", Style::default().fg(Color::Yellow))
+            }
+            StatusItem::Modified if file.is_modified() => {
+                Span::styled("[+]", Style::default().fg(Color::Yellow))
+            }
+            StatusItem::Modified => continue,
+            // The hex view has no lines or text, it counts bytes.
+            StatusItem::Position if file.hex.is_some() => {
+                let cursor = file.hex.as_ref().map_or(0, |hex| hex.cursor);
+                Span::raw(format!("Offset {:#x}", cursor))
+            }
+            StatusItem::Lines if file.hex.is_some() => {
+                let len = file.hex.as_ref().map_or(0, |hex| hex.bytes.len());
+                Span::raw(format!("{} bytes", len))
+            }
+            StatusItem::LineEnding | StatusItem::Encoding if file.hex.is_some() => continue,
+            // Columns count graphemes, so they match what is seen on screen.
+            StatusItem::Position => Span::raw(format!(
+                "Ln {}, Col {}",
+                file.cursor_y + 1,
+                file.cursor_x + 1
+            )),
+            StatusItem::Lines => Span::raw(format!("{} lines", file.buffer.len_lines())),
+            // "noeol" marks a file that does not end with a line break.
+            StatusItem::LineEnding => match file.buffer.ends_with_line_break() {
+                true => Span::raw(file.line_ending.to_string()),
+                false => Span::raw(format!("{} noeol", file.line_ending)),
+            },
+            StatusItem::Encoding => Span::raw(file.encoding.to_string()),
+            StatusItem::FileType => Span::raw(file.file_type),
+        };
+
+        if !spans.is_empty() {
+            spans.push(separator.clone());
+        }
+        spans.push(span);
+    }
+
+    if let Some(message) = &app.context.status_message {
+        spans.push(separator);
+        spans.push(Span::styled(
+            message.clone(),
+            Style::default().fg(Color::Yellow),
+        ));
+    }
+
+    let paragraph = Paragraph::new(Spans::from(spans))
+        .style(Style::default().bg(Color::Black).fg(Color::White));
+    frame.render_widget(paragraph, area);
 }
 
 fn draw_header<B: Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
@@ -37,10 +396,15 @@ fn draw_header<B: Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
         .file_list
         .iter()
         .map(|menu| {
-            Spans::from(Span::styled(
-                menu.name.to_str().unwrap(),
+            // Modified files are marked with a "*" after their name.
+            let mut spans = vec![Span::styled(
+                menu.name.to_string_lossy().to_string(),
                 Style::default().fg(Color::Green),
-            ))
+            )];
+            if menu.is_modified() {
+                spans.push(Span::styled("*", Style::default().fg(Color::Yellow)));
+            }
+            Spans::from(spans)
         })
         .collect();
 
@@ -59,7 +423,7 @@ fn draw_header<B: Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
 // It is made up the interaction menu and the editor piece.
 fn draw_viewport<B: Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
     let constraints = if app.context.current_window == WindowState::Navigation {
-        vec![Constraint::Length(15), Constraint::Percentage(80)]
+        vec![Constraint::Length(30), Constraint::Percentage(80)]
     } else {
         vec![Constraint::Percentage(0), Constraint::Percentage(100)]
     };
@@ -69,38 +433,143 @@ fn draw_viewport<B: Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
         .direction(Direction::Horizontal)
         .split(area);
 
+    // The editor is drawn first, because it decides which lines the gutter shows.
+    let gutter_width = gutter_width(app);
+    let editor_chunks = Layout::default()
+        .constraints([Constraint::Length(gutter_width), Constraint::Min(1)])
+        .direction(Direction::Horizontal)
+        .split(chunks[1]);
+
+    draw_editor(app, frame, editor_chunks[1]);
+    draw_gutter(app, frame, editor_chunks[0]);
+
     // Draw Left Side Panel
-    draw_editor(app, frame, chunks);
     draw_interaction_menu(app, frame, chunks);
 }
 
-fn draw_editor<B: Backend>(app: &mut App, frame: &mut Frame<B>, chunks: &[Rect]) {
-    let display_string = app
+// The gutter has two sign columns for markers, followed by the line numbers.
+// The numbers are as wide as the largest line number, but at least 3 digits.
+const SIGN_COLUMNS: u16 = 2;
+
+fn gutter_width(app: &mut App) -> u16 {
+    // The hex view has its own offset column.
+    if app.context.file_manager_window.current_file().hex.is_some() {
+        return 0;
+    }
+    if app.context.line_numbers == LineNumbers::Off {
+        return SIGN_COLUMNS;
+    }
+
+    let line_count = app
         .context
         .file_manager_window
         .current_file()
-        .get_display_text();
-    let display_text = Text::from(display_string);
+        .buffer
+        .len_lines();
+    let digits = line_count.to_string().len().max(3) as u16;
+
+    SIGN_COLUMNS + digits + 1
+}
+
+fn draw_gutter<B: Backend>(app: &mut App, frame: &mut Frame<B>, area: Rect) {
+    let line_numbers = app.context.line_numbers;
+    let digits = area.width.saturating_sub(SIGN_COLUMNS + 1) as usize;
+    let file = app.context.file_manager_window.current_file();
+    if file.hex.is_some() {
+        return;
+    }
+
+    let last_line = file.buffer.len_lines();
+    let first_line = file.scroll_offset;
+    let end_line = last_line.min(first_line + file.view_height);
+
+    let lines: Vec<Spans> = (first_line..end_line)
+        .map(|y| {
+            let modified = match file.markers.has(y, MarkerKind::Modified) {
+                true => Span::styled("│", Style::default().fg(Color::Yellow)),
+                false => Span::raw(" "),
+            };
+
+            // Diagnostics take precedence over bookmarks in the second sign column.
+            let sign = match (
+                file.markers.has(y, MarkerKind::Diagnostic),
+                file.markers.has(y, MarkerKind::Bookmark),
+            ) {
+                (true, _) => Span::styled("●", Style::default().fg(Color::Red)),
+                (false, true) => Span::styled("◆", Style::default().fg(Color::Cyan)),
+                (false, false) => Span::raw(" "),
+            };
+
+            let number = match line_numbers {
+                LineNumbers::Off => String::new(),
+                LineNumbers::Relative if y != file.cursor_y => {
+                    format!("{:>digits$} ", y.abs_diff(file.cursor_y), digits = digits)
+                }
+                _ => format!("{:>digits$} ", y + 1, digits = digits),
+            };
+
+            let number_style = if y == file.cursor_y {
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::DarkGray)
+            };
+
+            Spans::from(vec![modified, sign, Span::styled(number, number_style)])
+        })
+        .collect();
+
+    // The gutter has the same top border as the editor, so the lines align.
+    let paragraph = Paragraph::new(lines).block(Block::default().borders(Borders::TOP));
+    frame.render_widget(paragraph, area);
+}
+
+fn draw_editor<B: Backend>(app: &mut App, frame: &mut Frame<B>, area: Rect) {
+    let file = app.context.file_manager_window.current_file();
+
+    // The file scrolls to keep the cursor inside the area it is drawn in.
+    let inner = Block::default().borders(Borders::TOP).inner(area);
+    let display_text = match &mut file.hex {
+        Some(hex) => {
+            hex.set_view_height(inner.height as usize);
+            hex.get_display_text()
+        }
+        None => {
+            file.set_view_size(inner.width as usize, inner.height as usize);
+            file.get_display_text()
+        }
+    };
 
     let block = Block::default().borders(Borders::TOP).title(Span::styled(
-        app.context
-            .file_manager_window
-            .current_file()
-            .name
-            .to_str()
-            .unwrap(),
+        file.name.to_string_lossy().to_string(),
         Style::default()
             .fg(Color::Magenta)
             .add_modifier(Modifier::BOLD),
     ));
 
-    let paragraph = Paragraph::new(display_text)
-        .block(block)
-        .wrap(Wrap { trim: false });
-    frame.render_widget(paragraph, chunks[1]);
+    let paragraph = Paragraph::new(display_text).block(block);
+    frame.render_widget(paragraph, area);
 }
 
+// The left panel has the action menu on top and the file explorer below it.
+// The part that has the focus shows its selection.
 fn draw_interaction_menu<B: Backend>(app: &mut App, frame: &mut Frame<B>, chunks: &[Rect]) {
+    let panel_chunks = Layout::default()
+        .constraints([
+            Constraint::Length(widgets::navigation::ActionMenuWindow::COUNT as u16 + 1),
+            Constraint::Min(1),
+        ])
+        .split(chunks[0]);
+
+    let focus = app.context.navigation_window.focus;
+    let highlight_style = |focused: bool| match focused {
+        true => Style::default()
+            .fg(Color::Magenta)
+            .add_modifier(Modifier::BOLD),
+        false => Style::default(),
+    };
+
     let menu_items: Vec<ListItem> = widgets::navigation::ActionMenuWindow::iter()
         .map(|i| ListItem::new(vec![Spans::from(Span::raw(i.to_string()))]))
         .collect();
@@ -116,18 +585,66 @@ fn draw_interaction_menu<B: Backend>(app: &mut App, frame: &mut Frame<B>, chunks
                         .add_modifier(Modifier::BOLD),
                 )),
         )
-        .highlight_style(
-            Style::default()
-                .fg(Color::Magenta)
-                .add_modifier(Modifier::BOLD),
-        )
+        .highlight_style(highlight_style(focus == NavigationFocus::Menu))
         .highlight_symbol("> ");
 
     frame.render_stateful_widget(
         tasks,
-        chunks[0],
+        panel_chunks[0],
         &mut app.context.navigation_window.list_state,
     );
+
+    draw_explorer(
+        app,
+        frame,
+        panel_chunks[1],
+        highlight_style(focus == NavigationFocus::Explorer),
+    );
+}
+
+fn draw_explorer<B: Backend>(app: &mut App, frame: &mut Frame<B>, area: Rect, highlight: Style) {
+    let explorer = &mut app.context.navigation_window.explorer;
+
+    let items: Vec<ListItem> = explorer
+        .entries
+        .iter()
+        .map(|entry| {
+            let indent = "  ".repeat(entry.depth);
+            let line = match (entry.is_dir, entry.expanded) {
+                (true, true) => Span::styled(
+                    format!("{}▾ {}", indent, entry.name),
+                    Style::default().fg(Color::Blue),
+                ),
+                (true, false) => Span::styled(
+                    format!("{}▸ {}", indent, entry.name),
+                    Style::default().fg(Color::Blue),
+                ),
+                (false, _) => Span::raw(format!("{}  {}", indent, entry.name)),
+            };
+            ListItem::new(Spans::from(line))
+        })
+        .collect();
+
+    let root_name = explorer.root.file_name().map_or_else(
+        || explorer.root.display().to_string(),
+        |name| name.to_string_lossy().to_string(),
+    );
+
+    let tree = List::new(items)
+        .block(
+            Block::default()
+                .borders(Borders::RIGHT | Borders::TOP)
+                .title(Span::styled(
+                    root_name,
+                    Style::default()
+                        .fg(Color::Magenta)
+                        .add_modifier(Modifier::BOLD),
+                )),
+        )
+        .highlight_style(highlight)
+        .highlight_symbol("> ");
+
+    frame.render_stateful_widget(tree, area, &mut explorer.list_state);
 }
 
 fn draw_console<B>(f: &mut Frame<B>, area: Rect)
@@ -0,0 +1,223 @@
+use std::collections::{HashMap, HashSet};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant, SystemTime};
+
+use notify::event::{ModifyKind, RenameMode};
+use notify::{EventKind, RecommendedWatcher, RecursiveMode, Watcher};
+
+// Other programs change open files: a git checkout, a formatter, another editor.
+// The directories of the open files are watched, and an event from one of them
+// has the files checked against what they were when they were last read or
+// written. The files are also checked every few seconds, for file systems that
+// send no events, such as network mounts, or when watching is not possible.
+
+// How often the files are checked without a watcher, and with one.
+const POLL_INTERVAL: Duration = Duration::from_secs(2);
+const WATCHED_POLL_INTERVAL: Duration = Duration::from_secs(10);
+
+// Reading and decoding a file happens on the UI thread, so an unmodified file
+// larger than this is not reloaded whenever it changes, like a growing log.
+pub const RELOAD_LIMIT: u64 = 8 * 1024 * 1024;
+
+/// What a file on disk looked like, enough to tell that it was written or replaced.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DiskState {
+    modified: SystemTime,
+    len: u64,
+    dev: u64,
+    ino: u64,
+}
+
+impl DiskState {
+    /// The state of the file at path, following symlinks. None when there is no file.
+    pub fn read(path: &Path) -> Option<DiskState> {
+        let metadata = std::fs::metadata(path).ok()?;
+        Some(DiskState {
+            modified: metadata.modified().ok()?,
+            len: metadata.len(),
+            dev: metadata.dev(),
+            ino: metadata.ino(),
+        })
+    }
+
+    /// The size of the file in bytes.
+    pub fn size(&self) -> u64 {
+        self.len
+    }
+}
+
+pub struct FileWatcher {
+    // None when the platform or its limits do not allow watching.
+    watcher: Option<RecommendedWatcher>,
+    events: Receiver<notify::Result<notify::Event>>,
+    dirs: HashSet<PathBuf>,
+    // Files renamed within the watched directories, to the path they went to.
+    moves: HashMap<PathBuf, PathBuf>,
+    last_check: Option<Instant>,
+}
+
+impl FileWatcher {
+    pub fn new() -> FileWatcher {
+        let (sender, events) = channel();
+        FileWatcher {
+            watcher: notify::recommended_watcher(sender).ok(),
+            events,
+            dirs: HashSet::new(),
+            moves: HashMap::new(),
+            last_check: None,
+        }
+    }
+
+    // Watches the directories of the given files, and stops watching the
+    // directories that no longer have an open file in them.
+    pub fn watch<'a>(&mut self, paths: impl Iterator<Item = &'a Path>) {
+        let watcher = match &mut self.watcher {
+            Some(watcher) => watcher,
+            None => return,
+        };
+
+        let dirs: HashSet<PathBuf> = paths
+            .filter_map(|path| watched_path(path)?.parent().map(Path::to_path_buf))
+            .collect();
+
+        let watched = &self.dirs;
+        for dir in watched.difference(&dirs) {
+            let _ = watcher.unwatch(dir);
+        }
+        // A directory that cannot be watched is still polled.
+        let dirs = dirs
+            .into_iter()
+            .filter(|dir| {
+                watched.contains(dir) || watcher.watch(dir, RecursiveMode::NonRecursive).is_ok()
+            })
+            .collect();
+        self.dirs = dirs;
+    }
+
+    /// Whether the open files should be checked now: something changed in
+    /// their directories, or the poll interval passed since the last check.
+    pub fn should_check(&mut self) -> bool {
+        let mut changed = false;
+        while let Ok(event) = self.events.try_recv() {
+            let event = match event {
+                Ok(event) => event,
+                Err(_) => continue,
+            };
+            if let (EventKind::Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) =
+                (event.kind, event.paths.as_slice())
+            {
+                self.moves.insert(from.clone(), to.clone());
+            }
+            changed = true;
+        }
+
+        let interval = match self.watcher {
+            Some(_) => WATCHED_POLL_INTERVAL,
+            None => POLL_INTERVAL,
+        };
+        let due = self
+            .last_check
+            .is_none_or(|time| time.elapsed() >= interval);
+
+        if changed || due {
+            self.last_check = Some(Instant::now());
+            return true;
+        }
+        false
+    }
+
+    /// Where a file that is gone from path was renamed to, if that was seen.
+    pub fn take_move(&mut self, path: &Path) -> Option<PathBuf> {
+        self.moves.remove(&watched_path(path)?)
+    }
+
+    // Renames that were not about an open file are of no further use.
+    pub fn clear_moves(&mut self) {
+        self.moves.clear();
+    }
+}
+
+/// The path events name a file by: its directory resolved, as it is watched,
+/// and its own name, which may be gone by now.
+pub fn watched_path(path: &Path) -> Option<PathBuf> {
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    Some(dir.canonicalize().ok()?.join(path.file_name()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("rustex-watch-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn disk_state_changes_with_the_file() {
+        let dir = test_dir("disk-state");
+        let path = dir.join("notes.txt");
+        assert_eq!(DiskState::read(&path), None);
+
+        std::fs::write(&path, "one\n").unwrap();
+        let written = DiskState::read(&path).unwrap();
+        assert_eq!(written.size(), 4);
+        assert_eq!(DiskState::read(&path), Some(written));
+
+        std::fs::write(&path, "one\ntwo\n").unwrap();
+        assert_ne!(DiskState::read(&path), Some(written));
+
+        // A file replaced by another one is a change, even with the same contents.
+        let replacement = dir.join("notes.txt.new");
+        std::fs::write(&replacement, "one\ntwo\n").unwrap();
+        let before = DiskState::read(&path);
+        std::fs::rename(&replacement, &path).unwrap();
+        assert_ne!(DiskState::read(&path), before);
+
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(DiskState::read(&path), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn watched_path_resolves_the_directory() {
+        let dir = test_dir("watched-path");
+        std::fs::create_dir(dir.join("sub")).unwrap();
+        let path = dir.join("sub").join("..").join("gone.txt");
+
+        let expected = dir.canonicalize().unwrap().join("gone.txt");
+        assert_eq!(watched_path(&path), Some(expected));
+        assert_eq!(watched_path(&dir.join("missing").join("gone.txt")), None);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn checks_first_and_then_on_changes() {
+        let dir = test_dir("should-check");
+        let path = dir.join("notes.txt");
+        let mut watcher = FileWatcher::new();
+        watcher.watch(std::iter::once(path.as_path()));
+
+        assert!(watcher.should_check());
+        assert!(!watcher.should_check());
+
+        // Without a watcher, only the poll interval leads to a check.
+        if watcher.watcher.is_some() {
+            std::fs::write(&path, "one\n").unwrap();
+            let deadline = Instant::now() + Duration::from_secs(5);
+            while !watcher.should_check() {
+                assert!(Instant::now() < deadline, "no event for the written file");
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        }
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
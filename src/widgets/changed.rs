@@ -0,0 +1,35 @@
+use crossterm::event::KeyCode;
+
+use strum_macros::Display;
+use strum_macros::*;
+
+use crate::watch::DiskState;
+
+use super::diff_dialog::{DialogChoice, DiffDialog};
+
+#[derive(Debug, Display, Clone, Copy, EnumIter, EnumCount, PartialEq, Eq)]
+pub enum ChangedChoice {
+    Reload,
+    Diff,
+    Keep,
+}
+
+impl DialogChoice for ChangedChoice {
+    fn for_key(keycode: KeyCode) -> Option<ChangedChoice> {
+        match keycode {
+            KeyCode::Char('r') => Some(ChangedChoice::Reload),
+            KeyCode::Char('d') => Some(ChangedChoice::Diff),
+            KeyCode::Char('k') | KeyCode::Esc => Some(ChangedChoice::Keep),
+            _ => None,
+        }
+    }
+}
+
+// The file as another program left it on disk.
+pub struct DiskContents {
+    pub bytes: Vec<u8>,
+    pub disk_state: Option<DiskState>,
+}
+
+/// ChangedWindow asks what to do with a modified file that another program changed on disk.
+pub type ChangedWindow = DiffDialog<ChangedChoice, DiskContents>;
@@ -0,0 +1,67 @@
+use crossterm::event::KeyCode;
+
+use strum::*;
+use strum_macros::Display;
+use strum_macros::*;
+
+use super::Window;
+
+#[derive(Debug, Display, Clone, Copy, EnumIter, EnumCount, PartialEq, Eq)]
+pub enum ConfirmChoice {
+    Save,
+    Discard,
+    Cancel,
+}
+
+// The action that is waiting on the answer of the dialog.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PendingAction {
+    Quit { file_index: usize },
+    CloseFile { file_index: usize },
+}
+
+/// ConfirmWindow asks what to do with unsaved changes.
+pub struct ConfirmWindow {
+    pub message: String,
+    pub action: PendingAction,
+    pub selected: usize,
+    pub choice: Option<ConfirmChoice>,
+    // Shown when saving failed, so the user can pick again.
+    pub error: Option<String>,
+}
+
+impl Window for ConfirmWindow {
+    fn handle_keyboard_input(&mut self, keycode: KeyCode, _keyflags: u8) {
+        match keycode {
+            KeyCode::Char('s') => self.choice = Some(ConfirmChoice::Save),
+            KeyCode::Char('d') => self.choice = Some(ConfirmChoice::Discard),
+            KeyCode::Char('c') | KeyCode::Esc => self.choice = Some(ConfirmChoice::Cancel),
+            KeyCode::Enter => self.choice = ConfirmChoice::iter().nth(self.selected),
+
+            KeyCode::Left | KeyCode::BackTab => self.previous(),
+            KeyCode::Right | KeyCode::Tab => self.next(),
+
+            _ => (),
+        }
+    }
+}
+
+impl ConfirmWindow {
+    pub fn new(message: String, action: PendingAction) -> ConfirmWindow {
+        ConfirmWindow {
+            message,
+            action,
+            selected: 0,
+            choice: None,
+            error: None,
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % ConfirmChoice::COUNT;
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = (self.selected + ConfirmChoice::COUNT - 1) % ConfirmChoice::COUNT;
+    }
+}
@@ -0,0 +1,76 @@
+use crossterm::event::KeyCode;
+
+use strum::{EnumCount, IntoEnumIterator};
+
+use super::Window;
+
+/// The answers a DiffDialog offers, in the order of its buttons.
+pub trait DialogChoice: Copy + std::fmt::Display + IntoEnumIterator + EnumCount {
+    // The answer a key picks on its own, such as 'r' for Reload.
+    fn for_key(keycode: KeyCode) -> Option<Self>;
+}
+
+/// DiffDialog asks what to do with other contents found for an open file,
+/// such as a swap file, and can show how they differ from the file first.
+pub struct DiffDialog<C, T> {
+    pub message: String,
+    // The file the dialog is about, and the contents that were found for it.
+    pub file_index: usize,
+    pub contents: T,
+    pub selected: usize,
+    pub choice: Option<C>,
+
+    // The diff of the contents with the file, once asked for.
+    pub diff: Option<Vec<String>>,
+    pub scroll: usize,
+}
+
+impl<C: DialogChoice, T> Window for DiffDialog<C, T> {
+    fn handle_keyboard_input(&mut self, keycode: KeyCode, _keyflags: u8) {
+        if let Some(choice) = C::for_key(keycode) {
+            self.choice = Some(choice);
+            return;
+        }
+
+        match keycode {
+            KeyCode::Enter => self.choice = C::iter().nth(self.selected),
+
+            KeyCode::Left | KeyCode::BackTab => self.previous(),
+            KeyCode::Right | KeyCode::Tab => self.next(),
+
+            KeyCode::Up => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::Down => self.scroll_to(self.scroll + 1),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::PageDown => self.scroll_to(self.scroll + 10),
+
+            _ => (),
+        }
+    }
+}
+
+impl<C: DialogChoice, T> DiffDialog<C, T> {
+    pub fn new(message: String, file_index: usize, contents: T) -> DiffDialog<C, T> {
+        DiffDialog {
+            message,
+            file_index,
+            contents,
+            selected: 0,
+            choice: None,
+            diff: None,
+            scroll: 0,
+        }
+    }
+
+    pub fn next(&mut self) {
+        self.selected = (self.selected + 1) % C::COUNT;
+    }
+
+    pub fn previous(&mut self) {
+        self.selected = (self.selected + C::COUNT - 1) % C::COUNT;
+    }
+
+    fn scroll_to(&mut self, line: usize) {
+        let lines = self.diff.as_ref().map_or(0, Vec::len);
+        self.scroll = line.min(lines.saturating_sub(1));
+    }
+}
@@ -0,0 +1,274 @@
+use std::path::{Path, PathBuf};
+
+use crossterm::event::KeyCode;
+use tui::widgets::ListState;
+
+use super::Window;
+
+// The actions of the explorer that need the App, because they open files
+// or ask for a name first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ExplorerAction {
+    Open(PathBuf),
+    // New files and folders are created in this directory.
+    NewFile(PathBuf),
+    NewFolder(PathBuf),
+    Rename(PathBuf),
+    Delete(PathBuf),
+    RevealCurrentFile,
+}
+
+// A visible line of the tree.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplorerEntry {
+    pub path: PathBuf,
+    pub name: String,
+    pub depth: usize,
+    pub is_dir: bool,
+    pub expanded: bool,
+}
+
+/// ExplorerWindow is a tree of the files under a root directory.
+///
+/// Only the visible lines are kept, in the order they are drawn in.
+/// A directory is read when it is expanded, and its lines are removed
+/// again when it is collapsed, so large trees cost nothing until opened.
+pub struct ExplorerWindow {
+    pub root: PathBuf,
+    pub entries: Vec<ExplorerEntry>,
+    pub list_state: ListState,
+
+    // The action picked with a key press. The App takes it and runs it.
+    pub pending_action: Option<ExplorerAction>,
+}
+
+impl Window for ExplorerWindow {
+    fn handle_keyboard_input(&mut self, keycode: KeyCode, _keyflags: u8) {
+        match keycode {
+            KeyCode::Up => self.previous(),
+            KeyCode::Down => self.next(),
+
+            KeyCode::Enter | KeyCode::Right => self.open_selected(),
+            KeyCode::Left => self.collapse_selected(),
+
+            KeyCode::Char('n') => {
+                self.pending_action = Some(ExplorerAction::NewFile(self.selected_dir()))
+            }
+            KeyCode::Char('N') => {
+                self.pending_action = Some(ExplorerAction::NewFolder(self.selected_dir()))
+            }
+            KeyCode::Char('r') => {
+                self.pending_action = self
+                    .selected()
+                    .map(|e| ExplorerAction::Rename(e.path.clone()))
+            }
+            KeyCode::Char('d') | KeyCode::Delete => {
+                self.pending_action = self
+                    .selected()
+                    .map(|e| ExplorerAction::Delete(e.path.clone()))
+            }
+            KeyCode::Char('f') => self.pending_action = Some(ExplorerAction::RevealCurrentFile),
+            KeyCode::F(5) => self.refresh(),
+
+            _ => (),
+        }
+    }
+}
+
+impl ExplorerWindow {
+    pub fn new(root: PathBuf) -> ExplorerWindow {
+        let mut explorer = ExplorerWindow {
+            root,
+            entries: Vec::new(),
+            list_state: ListState::default(),
+            pending_action: None,
+        };
+        explorer.refresh();
+        explorer
+    }
+
+    pub fn set_root(&mut self, root: PathBuf) {
+        self.root = root;
+        self.entries.clear();
+        self.list_state.select(None);
+        self.refresh();
+    }
+
+    pub fn selected(&self) -> Option<&ExplorerEntry> {
+        self.list_state.selected().and_then(|i| self.entries.get(i))
+    }
+
+    // The directory new files go in: the selected directory,
+    // the directory of the selected file, or the root.
+    pub fn selected_dir(&self) -> PathBuf {
+        match self.selected() {
+            Some(entry) if entry.is_dir => entry.path.clone(),
+            Some(entry) => entry
+                .path
+                .parent()
+                .map_or_else(|| self.root.clone(), Path::to_path_buf),
+            None => self.root.clone(),
+        }
+    }
+
+    pub fn next(&mut self) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let index = self.list_state.selected().map_or(0, |i| i + 1);
+        self.list_state
+            .select(Some(index.min(self.entries.len() - 1)));
+    }
+
+    pub fn previous(&mut self) {
+        let index = self
+            .list_state
+            .selected()
+            .map_or(0, |i| i.saturating_sub(1));
+        self.list_state
+            .select(Some(index).filter(|_| !self.entries.is_empty()));
+    }
+
+    // Directories are expanded or collapsed, files are opened.
+    pub fn open_selected(&mut self) {
+        let index = match self.list_state.selected() {
+            Some(index) if index < self.entries.len() => index,
+            _ => return,
+        };
+
+        let entry = &self.entries[index];
+        if !entry.is_dir {
+            self.pending_action = Some(ExplorerAction::Open(entry.path.clone()));
+        } else if entry.expanded {
+            self.collapse(index);
+        } else {
+            self.expand(index);
+        }
+    }
+
+    // Collapses the selected directory, or moves to the parent of the selected line.
+    pub fn collapse_selected(&mut self) {
+        let index = match self.list_state.selected() {
+            Some(index) if index < self.entries.len() => index,
+            _ => return,
+        };
+
+        if self.entries[index].expanded {
+            self.collapse(index);
+            return;
+        }
+
+        let depth = self.entries[index].depth;
+        if let Some(parent) = self.entries[..index]
+            .iter()
+            .rposition(|entry| entry.depth < depth)
+        {
+            self.list_state.select(Some(parent));
+        }
+    }
+
+    fn expand(&mut self, index: usize) {
+        let entry = &mut self.entries[index];
+        entry.expanded = true;
+        let children = read_children(&entry.path, entry.depth + 1);
+        self.entries.splice(index + 1..index + 1, children);
+    }
+
+    fn collapse(&mut self, index: usize) {
+        let depth = self.entries[index].depth;
+        self.entries[index].expanded = false;
+
+        let end = self.entries[index + 1..]
+            .iter()
+            .position(|entry| entry.depth <= depth)
+            .map_or(self.entries.len(), |i| index + 1 + i);
+        self.entries.drain(index + 1..end);
+    }
+
+    // Reads the tree again, e.g. after files were created or removed.
+    // The same directories stay expanded and the same line stays selected,
+    // or the line at the same position when it is gone.
+    pub fn refresh(&mut self) {
+        let expanded: Vec<PathBuf> = self
+            .entries
+            .iter()
+            .filter(|entry| entry.expanded)
+            .map(|entry| entry.path.clone())
+            .collect();
+        let selected = self.selected().map(|entry| entry.path.clone());
+
+        self.entries = read_children(&self.root, 0);
+        let mut index = 0;
+        while index < self.entries.len() {
+            if expanded.contains(&self.entries[index].path) {
+                self.expand(index);
+            }
+            index += 1;
+        }
+
+        let index = selected
+            .and_then(|path| self.entries.iter().position(|e| e.path == path))
+            .or(self.list_state.selected())
+            .unwrap_or(0)
+            .min(self.entries.len().saturating_sub(1));
+        self.list_state
+            .select(Some(index).filter(|_| !self.entries.is_empty()));
+    }
+
+    // Expands the directories down to a file and selects it.
+    // Returns false when the file is not under the root.
+    pub fn reveal(&mut self, path: &Path) -> bool {
+        let path = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+        let root = self
+            .root
+            .canonicalize()
+            .unwrap_or_else(|_| self.root.clone());
+        let relative = match path.strip_prefix(&root) {
+            Ok(relative) => relative.to_path_buf(),
+            Err(_) => return false,
+        };
+
+        let mut current = self.root.clone();
+        let mut index: Option<usize> = None;
+        for component in relative.components() {
+            // Expand the directory found in the previous step.
+            if let Some(index) = index {
+                if !self.entries[index].expanded {
+                    self.expand(index);
+                }
+            }
+
+            current.push(component);
+            index = self.entries.iter().position(|entry| entry.path == current);
+            if index.is_none() {
+                return false;
+            }
+        }
+
+        self.list_state.select(index);
+        index.is_some()
+    }
+}
+
+// Reads the entries of a directory, directories first and then by name.
+// Directories that cannot be read show up empty.
+fn read_children(dir: &Path, depth: usize) -> Vec<ExplorerEntry> {
+    let read_dir = match std::fs::read_dir(dir) {
+        Ok(read_dir) => read_dir,
+        Err(_) => return Vec::new(),
+    };
+
+    let mut children: Vec<ExplorerEntry> = read_dir
+        .filter_map(Result::ok)
+        .map(|entry| ExplorerEntry {
+            name: entry.file_name().to_string_lossy().to_string(),
+            is_dir: entry.path().is_dir(),
+            path: entry.path(),
+            depth,
+            expanded: false,
+        })
+        .collect();
+
+    children.sort_by(|a, b| b.is_dir.cmp(&a.is_dir).then(a.name.cmp(&b.name)));
+    children
+}
@@ -1,10 +1,29 @@
+use std::cell::RefCell;
 use std::cmp::min;
 use std::ffi::OsString;
 use std::io::Write;
 use std::path::Path;
+use std::rc::Rc;
+use std::time::{Duration, Instant};
 
 use crossterm::event::KeyCode;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans, Text};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
 
+use crate::buffer::{LineEnding, TextBuffer};
+use crate::clipboard::Clipboard;
+use crate::encoding::{self, FileEncoding};
+use crate::error::Error;
+use crate::filetype;
+use crate::history::{Edit, History};
+use crate::history_store;
+use crate::markers::{MarkerKind, Markers};
+use crate::save::{self, Backup};
+use crate::swap::{self, SwapWriter};
+use crate::watch::DiskState;
+use crate::widgets::hex::{HexWindow, BYTES_PER_ROW};
 use crate::widgets::Window;
 
 #[derive(Clone, Default, Debug)]
@@ -12,31 +31,134 @@ pub struct FileWindow {
     pub name: OsString,
     pub path: Option<Box<Path>>,
 
+    // cursor_x is a grapheme column in the line, not a byte or char index.
     pub cursor_x: usize,
     pub cursor_y: usize,
 
+    // The selection start is where the selection was anchored,
+    // the selection end always follows the cursor.
     pub selection_start_x: usize,
     pub selection_start_y: usize,
     pub selection_end_x: usize,
     pub selection_end_y: usize,
     pub selection_active: bool,
 
-    pub lines: Vec<String>,
-    pub scroll_offset: usize, // Check ListState offset as an example of how to implement
+    pub buffer: TextBuffer,
+    pub line_ending: LineEnding,
+    // The encoding the file is read and saved in.
+    pub encoding: FileEncoding,
+    pub file_type: &'static str,
+    // The history revision that was last saved or opened.
+    pub saved_revision: u64,
+    pub history: History,
+    // Whether the history is written to the history store on save.
+    pub persistent_history: bool,
+    // Whether and where the previous version is kept on save.
+    pub backup: Backup,
+    pub clipboard: Rc<RefCell<Clipboard>>,
+    // Set by Alt+R until the next key names a register.
+    pub register_pending: bool,
+    pub markers: Markers,
+
+    // The swap file is written in the background by the writer shared with the file manager.
+    pub swap_writer: Rc<SwapWriter>,
+    // The key of the swap file this editor wrote, the revision in it, and when.
+    // swap_key is None while this editor has no swap file for the file.
+    pub swap_key: Option<String>,
+    pub swap_revision: Option<u64>,
+    pub swap_time: Option<Instant>,
+    // Set once the swap file found when opening the file was dealt with.
+    pub swap_checked: bool,
+    // Another running editor writes the swap file of this file, so this one does not.
+    pub swap_foreign: bool,
+
+    // The file on disk as it was when it was last read or saved,
+    // None when there was no file. Another program changed it when it differs.
+    pub disk_state: Option<DiskState>,
+    // The file was deleted from disk by another program, and is only in the editor now.
+    pub deleted: bool,
+    // The file changed on disk, but was too large to be reloaded on its own.
+    pub stale: bool,
+    // The text is written to stdout when the editor exits, with --stdout.
+    pub piped: bool,
+
+    // scroll_offset is the first visible line,
+    // scroll_x the first visible display column.
+    pub scroll_offset: usize,
+    pub scroll_x: usize,
+    // The size of the area the file was last drawn in.
+    pub view_width: usize,
+    pub view_height: usize,
+
+    // A binary file is shown and edited as bytes in a hex view instead of the buffer.
+    pub hex: Option<HexWindow>,
 }
 
+// The number of lines and columns kept visible around the cursor when scrolling.
+const SCROLL_OFF: usize = 3;
+// The least time between two writes of the swap file of a file being edited.
+const SWAP_INTERVAL: Duration = Duration::from_secs(2);
+
 impl Window for FileWindow {
-    fn handle_keyboard_input(&mut self, keycode: KeyCode, _keyflags: u8) {
-        match keycode {
-            KeyCode::Char(c) => self.insert_char(c),
-            KeyCode::Enter => self.insert_new_line(),
-            KeyCode::Backspace => self.backspace_char(),
-            KeyCode::Delete => self.delete_char(),
-
-            KeyCode::Left => self.move_cursor_x(-1),
-            KeyCode::Right => self.move_cursor_x(1),
-            KeyCode::Up => self.move_cursor_y(-1),
-            KeyCode::Down => self.move_cursor_y(1),
+    // keyflags = (BLANK, alt, control, shift) 0b0111
+    fn handle_keyboard_input(&mut self, keycode: KeyCode, keyflags: u8) {
+        if let Some(hex) = &mut self.hex {
+            hex.handle_keyboard_input(keycode, keyflags);
+            return;
+        }
+
+        // The key after Alt+R names the register of the next copy, cut or paste.
+        if self.register_pending {
+            self.register_pending = false;
+            if let (KeyCode::Char(name), 0b0000 | 0b0001) = (keycode, keyflags) {
+                self.clipboard.borrow_mut().select_register(name);
+            }
+            return;
+        }
+
+        match (keycode, keyflags) {
+            (KeyCode::Char('z'), 0b0010) => self.undo(),
+            (KeyCode::Char('y'), 0b0010) => self.redo(),
+
+            (KeyCode::F(2), _) => self.markers.toggle(self.cursor_y, MarkerKind::Bookmark),
+
+            (KeyCode::Char('c'), 0b0010) => self.copy_selection(),
+            (KeyCode::Char('x'), 0b0010) => self.cut_selection(),
+            (KeyCode::Char('v'), 0b0010) => {
+                let text = self.clipboard.borrow_mut().paste();
+                if let Some(text) = text {
+                    self.paste_text(&text);
+                }
+            }
+            (KeyCode::Char('r'), 0b0100) => self.register_pending = true,
+            // Alt+V pastes older entries from the yank ring.
+            (KeyCode::Char('v'), 0b0100) => {
+                let text = self.clipboard.borrow_mut().paste_older();
+                if let Some(text) = text {
+                    self.paste_text(&text);
+                }
+            }
+
+            (KeyCode::Char(c), 0b0000 | 0b0001) => self.insert_char(c),
+            (KeyCode::Enter, _) => self.insert_new_line(),
+            (KeyCode::Backspace, _) => self.backspace_char(),
+            (KeyCode::Delete, _) => self.delete_char(),
+
+            // Holding shift extends the selection while moving.
+            (KeyCode::Left, _) => self.move_with_selection(keyflags, |f| f.move_cursor_x(-1)),
+            (KeyCode::Right, _) => self.move_with_selection(keyflags, |f| f.move_cursor_x(1)),
+            (KeyCode::Up, _) => self.move_with_selection(keyflags, |f| f.move_cursor_y(-1)),
+            (KeyCode::Down, _) => self.move_with_selection(keyflags, |f| f.move_cursor_y(1)),
+            (KeyCode::Home, flags) if flags & 0b0010 != 0 => {
+                self.move_with_selection(keyflags, |f| f.move_cursor_start())
+            }
+            (KeyCode::End, flags) if flags & 0b0010 != 0 => {
+                self.move_with_selection(keyflags, |f| f.move_cursor_last())
+            }
+            (KeyCode::Home, _) => self.move_with_selection(keyflags, |f| f.move_cursor_home()),
+            (KeyCode::End, _) => self.move_with_selection(keyflags, |f| f.move_cursor_end()),
+            (KeyCode::PageUp, _) => self.move_with_selection(keyflags, |f| f.page_up()),
+            (KeyCode::PageDown, _) => self.move_with_selection(keyflags, |f| f.page_down()),
 
             _ => (),
         }
@@ -48,69 +170,731 @@ impl FileWindow {
         FileWindow {
             name: OsString::from(name),
             path: None,
-            lines: vec!["".to_string()],
+            buffer: TextBuffer::new(),
+            file_type: filetype::detect(Path::new(name), ""),
             ..Default::default()
         }
     }
-    pub fn open_file(path: Box<Path>) -> Result<FileWindow, std::io::Error> {
-        let name = path.file_name().unwrap().to_os_string();
 
-        let lines = std::fs::read_to_string(&path)
-            .unwrap()
-            .lines()
-            .map(|s| s.to_string())
-            .collect();
+    // An empty file for a path that does not exist yet.
+    // It is created on disk when it is first saved.
+    pub fn new_file(path: Box<Path>) -> FileWindow {
+        let name = path.file_name().unwrap_or(path.as_os_str()).to_os_string();
+
+        FileWindow {
+            name,
+            file_type: filetype::detect(&path, ""),
+            path: Some(path),
+            ..Default::default()
+        }
+    }
+
+    // An unnamed file with the text read from a reader, such as stdin.
+    pub fn from_reader<R: std::io::Read>(name: &str, mut reader: R) -> Result<FileWindow, Error> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        let (text, encoding) = encoding::decode_detected(&bytes);
+        let buffer = TextBuffer::from(text.as_str());
+        let line_ending = buffer.detect_line_ending();
+        let file_type = filetype::detect(Path::new(name), &buffer.line_text(0).to_string());
+
+        Ok(FileWindow {
+            name: OsString::from(name),
+            buffer,
+            line_ending,
+            encoding,
+            file_type,
+            ..Default::default()
+        })
+    }
+
+    pub fn open_file(path: Box<Path>) -> Result<FileWindow, Error> {
+        if path.is_dir() {
+            return Err(Error::IsDirectory(path.to_path_buf()));
+        }
+        let name = file_name(&path)?;
+
+        // The state is read first, so a write while reading is seen as a change later.
+        let disk_state = DiskState::read(&path);
+        let bytes = std::fs::read(&path).map_err(|e| Error::io(&path, e))?;
+        if encoding::is_binary(&bytes) {
+            return Ok(FileWindow {
+                name,
+                path: Some(path),
+                file_type: "Binary",
+                disk_state,
+                hex: Some(HexWindow::new(bytes)),
+                ..Default::default()
+            });
+        }
+
+        let (text, encoding) = encoding::decode_detected(&bytes);
+        let buffer = TextBuffer::from(text.as_str());
+
+        // Undo history from an earlier session is only restored
+        // when the file has not changed since then.
+        let history = history_store::load(&path, &buffer);
+        let persistent_history = history.is_some();
+        let history = history.unwrap_or_default();
+        let saved_revision = history.revision();
+
+        let line_ending = buffer.detect_line_ending();
+        let file_type = filetype::detect(&path, &buffer.line_text(0).to_string());
 
         let path = Some(path);
 
         Ok(FileWindow {
             name,
             path,
-            lines,
+            buffer,
+            line_ending,
+            encoding,
+            file_type,
+            history,
+            saved_revision,
+            persistent_history,
+            disk_state,
             ..Default::default()
         })
     }
 
-    pub fn save_file(&self) -> Result<(), std::io::Error> {
-        let path = self.path.clone().unwrap();
+    pub fn save_file(&mut self) -> Result<(), Error> {
+        let path = self.path.clone().ok_or(Error::NoPath)?;
+
+        let contents = self.encoded()?;
+        save::write_file(&path, &contents, &self.backup)?;
+        self.disk_state = DiskState::read(&path);
+        self.deleted = false;
+        self.stale = false;
+
+        self.markers.clear(MarkerKind::Modified);
+        self.saved_revision = self.history.revision();
+        self.remove_swap();
+        if let Some(hex) = &mut self.hex {
+            hex.mark_saved();
+        }
+
+        // The file itself is saved at this point,
+        // so failing to store the history does not fail the save.
+        if self.persistent_history {
+            let _ = history_store::save(&path, &self.buffer, &self.history);
+        }
+
+        Ok(())
+    }
+
+    // Saves the file in another encoding, which it keeps from then on.
+    // The encoding stays as it was when saving fails.
+    pub fn save_with_encoding(&mut self, encoding: FileEncoding) -> Result<(), Error> {
+        let previous = std::mem::replace(&mut self.encoding, encoding);
+        let saved = self.save_file();
+        if saved.is_err() {
+            self.encoding = previous;
+        }
+        saved
+    }
 
-        let mut file = std::fs::File::create(path)?;
-        file.write_all(self.lines.join("\n").as_bytes())?;
+    // Reads the file again, decoded in another encoding. Unsaved changes
+    // would be lost, so the caller makes sure there are none.
+    pub fn reopen_with_encoding(&mut self, encoding: FileEncoding) -> Result<(), Error> {
+        let path = self.path.clone().ok_or(Error::NoPath)?;
+        let disk_state = DiskState::read(&path);
+        let bytes = std::fs::read(&path).map_err(|e| Error::io(&path, e))?;
+        let (text, encoding) = encoding::decode(&bytes, encoding).ok_or(Error::Decode {
+            path: path.to_path_buf(),
+            encoding: encoding.encoding.name(),
+        })?;
 
+        self.replace_text(&text);
+        self.encoding = encoding;
+        self.disk_state = disk_state;
+        self.markers = Markers::default();
         Ok(())
     }
 
-    pub fn save_as_file(&mut self, path: Box<Path>) -> Result<(), std::io::Error> {
-        self.name = path.file_name().unwrap().to_os_string();
+    // Replaces the whole text, unmodified, with the cursor kept where it was.
+    // The history starts anew, as its edits were made to the text before.
+    fn replace_text(&mut self, text: &str) {
+        let (cursor_x, cursor_y) = (self.cursor_x, self.cursor_y);
+        self.clear_selection();
+        self.buffer = TextBuffer::from(text);
+        self.line_ending = self.buffer.detect_line_ending();
+        self.history = History::new();
+        self.saved_revision = self.history.revision();
+
+        self.go_to(cursor_y + 1, cursor_x + 1);
+    }
+
+    // The text of the file in its encoding, as it is saved.
+    // A file in the hex view is saved as exactly its bytes.
+    pub fn encoded(&self) -> Result<Vec<u8>, Error> {
+        if let Some(hex) = &self.hex {
+            return Ok(hex.bytes.clone());
+        }
+
+        encoding::encode(&self.buffer.to_string(), self.encoding).map_err(|c| Error::Encode {
+            path: self
+                .path
+                .as_ref()
+                .map_or_else(|| self.name.clone().into(), |path| path.to_path_buf()),
+            encoding: self.encoding.encoding.name(),
+            c,
+        })
+    }
+
+    pub fn save_as_file(&mut self, path: Box<Path>) -> Result<(), Error> {
+        if path.is_dir() {
+            return Err(Error::IsDirectory(path.to_path_buf()));
+        }
+        self.name = file_name(&path)?;
+        self.file_type = filetype::detect(&path, &self.buffer.line_text(0).to_string());
         self.path = Some(path);
 
         self.save_file()
     }
 
+    // Writes the buffer to a new file beside path, named "path.save", or
+    // "path.save.1" and so on when that exists. Nothing is overwritten.
+    pub fn write_copy(&self, path: &Path) -> Result<std::path::PathBuf, Error> {
+        let mut copy_path = path.as_os_str().to_os_string();
+        copy_path.push(".save");
+
+        for n in 0..100 {
+            let mut candidate = copy_path.clone();
+            if n > 0 {
+                candidate.push(format!(".{}", n));
+            }
+            let candidate = std::path::PathBuf::from(candidate);
+
+            let file = match std::fs::OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&candidate)
+            {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+                Err(e) => return Err(Error::io(&candidate, e)),
+            };
+
+            // Text that the encoding cannot hold is still kept, in UTF-8.
+            let contents = self
+                .encoded()
+                .unwrap_or_else(|_| self.buffer.to_string().into_bytes());
+            let mut writer = std::io::BufWriter::new(file);
+            writer
+                .write_all(&contents)
+                .and_then(|()| writer.flush())
+                .map_err(|e| Error::io(&candidate, e))?;
+            return Ok(candidate);
+        }
+
+        Err(Error::io(
+            path,
+            std::io::Error::new(std::io::ErrorKind::AlreadyExists, "too many saved copies"),
+        ))
+    }
+
+    // Writes the swap file when the text changed since it was last written.
+    // The swap file goes away again when undoing back to the saved text.
+    pub fn write_swap(&mut self) {
+        // Swap files hold text, the bytes of a hex view are not kept.
+        if self.swap_foreign || self.hex.is_some() {
+            return;
+        }
+
+        if !self.is_modified() {
+            self.remove_swap();
+            return;
+        }
+
+        let revision = self.history.revision();
+        let recent = self
+            .swap_time
+            .is_some_and(|time| time.elapsed() < SWAP_INTERVAL);
+        if self.swap_revision == Some(revision) || recent {
+            return;
+        }
+
+        // An unnamed file keeps its key until it is saved under a path.
+        let key = match (&self.path, &self.swap_key) {
+            (Some(path), _) => match swap::key(path) {
+                Some(key) => key,
+                None => return,
+            },
+            (None, Some(key)) => key.clone(),
+            (None, None) => self.swap_writer.unnamed_key(&self.name.to_string_lossy()),
+        };
+        // The file was renamed since its swap file was written.
+        if let Some(old_key) = self.swap_key.replace(key.clone()) {
+            if old_key != key {
+                self.swap_writer.remove(&old_key);
+            }
+        }
+
+        self.swap_time = Some(Instant::now());
+        self.swap_revision = Some(revision);
+        self.swap_writer.write(&key, &self.buffer);
+    }
+
+    pub fn remove_swap(&mut self) {
+        self.swap_revision = None;
+        if let Some(key) = self.swap_key.take() {
+            self.swap_writer.remove(&key);
+        }
+    }
+
+    // Replaces the whole text with text recovered from a swap file,
+    // as a single undo step, so the recovered changes can be undone.
+    pub fn recover(&mut self, text: &str) {
+        let cursor = self.cursor_char_idx();
+
+        self.clear_selection();
+        self.history.begin(cursor);
+        self.remove_text(0..self.buffer.len_chars());
+        self.insert_text(0, text, false);
+        self.history.commit();
+
+        self.set_cursor_char_idx(cursor);
+    }
+
+    // A file is modified when its contents differ from the last saved revision.
+    // Undoing back to the saved revision makes it unmodified again.
+    // A file deleted from disk counts as modified until it is saved again.
+    pub fn is_modified(&self) -> bool {
+        self.has_changes() || self.deleted
+    }
+
+    // Whether there are edits since the file was last saved or read.
+    pub fn has_changes(&self) -> bool {
+        self.history.revision() != self.saved_revision
+            || self.hex.as_ref().is_some_and(HexWindow::is_modified)
+    }
+
+    // Replaces the contents with what another program wrote to the file,
+    // as an undo step, so a formatter run can be undone.
+    pub fn reload(&mut self, bytes: Vec<u8>, disk_state: Option<DiskState>) {
+        self.disk_state = disk_state;
+        self.deleted = false;
+        self.stale = false;
+
+        if self.hex.is_some() || encoding::is_binary(&bytes) {
+            let cursor = self.hex.as_ref().map_or(0, |hex| hex.cursor);
+            let mut hex = HexWindow::new(bytes);
+            hex.set_cursor(cursor);
+            self.hex = Some(hex);
+            self.file_type = "Binary";
+        } else {
+            let (text, encoding) = encoding::decode(&bytes, self.encoding)
+                .unwrap_or_else(|| encoding::decode_detected(&bytes));
+            self.encoding = encoding;
+            self.replace_changed_text(&text);
+        }
+
+        self.markers.clear(MarkerKind::Modified);
+        self.remove_swap();
+    }
+
+    // Replaces the text with text read from disk, as a single undo step that
+    // leaves the file unmodified. Only what lies between the unchanged start
+    // and end is replaced, so a file that grows records just what was appended,
+    // and not two copies of the whole file for every reload.
+    fn replace_changed_text(&mut self, text: &str) {
+        let (cursor_x, cursor_y) = (self.cursor_x, self.cursor_y);
+        let old_len = self.buffer.len_chars();
+        let new_len = text.chars().count();
+
+        let old_text = self.buffer.slice(0..old_len);
+        let start = old_text
+            .chars()
+            .zip(text.chars())
+            .take_while(|(old, new)| old == new)
+            .count();
+        let end = old_text
+            .chars_at(old_len)
+            .reversed()
+            .zip(text.chars().rev())
+            .take(old_len.min(new_len) - start)
+            .take_while(|(old, new)| old == new)
+            .count();
+
+        let byte_idx = |char_idx| {
+            text.char_indices()
+                .nth(char_idx)
+                .map_or(text.len(), |(i, _)| i)
+        };
+        let inserted = &text[byte_idx(start)..byte_idx(new_len - end)];
+
+        self.clear_selection();
+        self.history.begin(self.cursor_char_idx());
+        if start < old_len - end {
+            self.remove_text(start..old_len - end);
+        }
+        if !inserted.is_empty() {
+            self.insert_text(start, inserted, false);
+        }
+        self.history.commit();
+        self.saved_revision = self.history.revision();
+
+        self.line_ending = self.buffer.detect_line_ending();
+        self.go_to(cursor_y + 1, cursor_x + 1);
+    }
+
+    // Switches between the text and the hex view. The contents go through
+    // their encoded bytes, so the caller makes sure there are no unsaved changes.
+    pub fn toggle_hex_view(&mut self) -> Result<(), Error> {
+        let bytes = self.encoded()?;
+
+        match self.hex.take() {
+            Some(_) => {
+                let (text, encoding) = encoding::decode_detected(&bytes);
+                self.buffer = TextBuffer::from(text.as_str());
+                self.line_ending = self.buffer.detect_line_ending();
+                self.encoding = encoding;
+                self.history = History::new();
+                self.saved_revision = self.history.revision();
+                self.markers = Markers::default();
+                self.file_type = filetype::detect(
+                    self.path.as_deref().unwrap_or(Path::new(&self.name)),
+                    &self.buffer.line_text(0).to_string(),
+                );
+                self.go_to(1, 1);
+            }
+            None => {
+                self.hex = Some(HexWindow::new(bytes));
+                self.file_type = "Binary";
+            }
+        }
+        Ok(())
+    }
+
+    // The char index in the buffer of a grapheme column in a line.
+    pub fn char_idx_at(&self, x: usize, y: usize) -> usize {
+        self.buffer.line_to_char(y) + self.buffer.grapheme_to_char(y, x)
+    }
+
+    // The char index in the buffer that the cursor points at.
+    pub fn cursor_char_idx(&self) -> usize {
+        self.char_idx_at(self.cursor_x, self.cursor_y)
+    }
+
+    // Moves the cursor to a char index in the buffer.
+    // The cursor always lands on a grapheme boundary.
+    pub fn set_cursor_char_idx(&mut self, char_idx: usize) {
+        let char_idx = min(char_idx, self.buffer.len_chars());
+        self.cursor_y = self.buffer.char_to_line(char_idx);
+
+        let char_x = char_idx - self.buffer.line_to_char(self.cursor_y);
+        self.cursor_x = self.buffer.char_to_grapheme(self.cursor_y, char_x);
+        self.reset_cursor();
+    }
+
+    // Moves the cursor to a line and column, both counted from 1 as they are shown.
+    pub fn go_to(&mut self, line: usize, column: usize) {
+        // In the hex view lines are rows of bytes and columns are bytes.
+        if let Some(hex) = &mut self.hex {
+            let row = line.saturating_sub(1) * BYTES_PER_ROW;
+            hex.set_cursor(row + column.saturating_sub(1));
+            return;
+        }
+
+        self.history.break_group();
+        self.clear_selection();
+        self.cursor_y = line.saturating_sub(1);
+        self.cursor_x = column.saturating_sub(1);
+        self.reset_cursor();
+    }
+
+    // Selects the next occurrence of text after the cursor, wrapping around
+    // at the end of the file. Returns false when there is none.
+    pub fn find(&mut self, text: &str) -> bool {
+        if self.hex.is_some() {
+            return false;
+        }
+
+        // Searching again from a match moves on to the next one.
+        let from = match self.selection_range() {
+            Some(range) => range.start + 1,
+            None => self.cursor_char_idx(),
+        };
+
+        let start = match self.buffer.find(text, from) {
+            Some(start) => start,
+            None => return false,
+        };
+
+        self.history.break_group();
+        self.set_cursor_char_idx(start);
+        self.selection_active = true;
+        self.selection_start_x = self.cursor_x;
+        self.selection_start_y = self.cursor_y;
+
+        self.set_cursor_char_idx(start + text.chars().count());
+        self.selection_end_x = self.cursor_x;
+        self.selection_end_y = self.cursor_y;
+        true
+    }
+
+    // The char range in the buffer of the grapheme at a grapheme column.
+    fn grapheme_char_range(&self, grapheme_x: usize) -> std::ops::Range<usize> {
+        let line_start = self.buffer.line_to_char(self.cursor_y);
+        let start = self.buffer.grapheme_to_char(self.cursor_y, grapheme_x);
+        let end = self.buffer.grapheme_to_char(self.cursor_y, grapheme_x + 1);
+        line_start + start..line_start + end
+    }
+
+    // The number of terminal columns between the start of the line and the cursor.
+    // Wide CJK characters take two columns and combining marks take none.
+    pub fn cursor_display_x(&self) -> usize {
+        self.buffer
+            .line_graphemes(self.cursor_y)
+            .iter()
+            .take(self.cursor_x)
+            .map(|g| g.width())
+            .sum()
+    }
+
+    // Applies an edit to the buffer, records it in the history
+    // and moves the cursor to cursor_after.
+    // Consecutive edits with typing set are grouped into one undo step.
+    // Every edit drops the selection, an empty one too, as its positions
+    // no longer point at the same text.
+    fn apply_edit(&mut self, edit: Edit, cursor_after: usize, typing: bool) {
+        let cursor_before = self.cursor_char_idx();
+        self.clear_selection();
+
+        self.markers.update_for_edit(&self.buffer, &edit);
+        edit.apply(&mut self.buffer);
+        self.history
+            .record(edit, cursor_before, cursor_after, typing);
+
+        self.set_cursor_char_idx(cursor_after);
+    }
+
+    fn insert_text(&mut self, char_idx: usize, text: &str, typing: bool) {
+        let cursor_after = char_idx + text.chars().count();
+        self.apply_edit(Edit::insert(char_idx, text), cursor_after, typing);
+    }
+
+    fn remove_text(&mut self, char_range: std::ops::Range<usize>) {
+        let cursor_after = char_range.start;
+        let edit = Edit::remove(&self.buffer, char_range);
+        self.apply_edit(edit, cursor_after, false);
+    }
+
+    // Removes the selected text. Returns false if nothing was selected.
+    pub fn delete_selection(&mut self) -> bool {
+        match self.selection_range() {
+            Some(range) => {
+                self.clear_selection();
+                self.remove_text(range);
+                true
+            }
+            None => {
+                self.clear_selection();
+                false
+            }
+        }
+    }
+
+    // Typing over a selection replaces it. The removal and the insert
+    // are a single undo step.
+    fn replace_selection(&mut self, text: &str) {
+        self.history.begin(self.cursor_char_idx());
+        self.delete_selection();
+        self.insert_text(self.cursor_char_idx(), text, false);
+        self.history.commit();
+    }
+
+    pub fn copy_selection(&mut self) {
+        if let Some(text) = self.selected_text() {
+            self.clipboard.borrow_mut().copy(text);
+        }
+    }
+
+    pub fn cut_selection(&mut self) {
+        self.copy_selection();
+        self.delete_selection();
+    }
+
+    // Inserts text at the cursor, replacing the selection if there is one.
+    // A multi-line paste is still a single undo step.
+    pub fn paste_text(&mut self, text: &str) {
+        if self.hex.is_some() {
+            return;
+        }
+        let text = &self.convert_line_breaks(text, self.insert_point());
+        if self.selection_range().is_some() {
+            self.replace_selection(text);
+        } else {
+            self.history.break_group();
+            self.insert_text(self.cursor_char_idx(), text, false);
+        }
+    }
+
+    pub fn undo(&mut self) {
+        let step = self.history.undo();
+        self.apply_history_step(step);
+    }
+
+    pub fn redo(&mut self) {
+        let step = self.history.redo();
+        self.apply_history_step(step);
+    }
+
+    // Applies the edits of an undo or redo without recording them again.
+    fn apply_history_step(&mut self, step: Option<(Vec<Edit>, usize)>) {
+        self.clear_selection();
+
+        if let Some((edits, cursor)) = step {
+            // Undoing a line ending conversion brings the old style back. Only
+            // edits with a carriage return change the style, and a file without
+            // line breaks keeps the style it has.
+            let restyled = edits.iter().any(|edit| edit.text.contains('\r'));
+            for edit in edits {
+                self.markers.update_for_edit(&self.buffer, &edit);
+                edit.apply(&mut self.buffer);
+            }
+            if restyled && self.buffer.len_lines() > 1 {
+                self.line_ending = self.buffer.detect_line_ending();
+            }
+            self.set_cursor_char_idx(cursor);
+        }
+    }
+
     /// This can be used to add a new line to the file.
     pub fn insert_line(&mut self, line: String) {
-        self.lines.insert(self.cursor_y + 1, line);
-        self.move_cursor_y(1);
-        self.reset_cursor();
+        let line_end =
+            self.buffer.line_to_char(self.cursor_y) + self.buffer.line_len(self.cursor_y);
+        self.insert_text(line_end, &format!("\n{}", line), false);
     }
 
     /// This can be used to add a new line at the cursor position.
     pub fn insert_new_line(&mut self) {
-        let (one, two) = self.lines[self.cursor_y].split_at(self.cursor_x);
-        let one = one.to_string();
-        let two = two.to_string();
+        let line_break = self.line_break(self.insert_point());
+        if self.selection_range().is_some() {
+            self.replace_selection(line_break);
+        } else {
+            self.insert_text(self.cursor_char_idx(), line_break, false);
+        }
+    }
 
-        self.lines.insert(self.cursor_y + 1, String::new());
+    // Where typed or pasted text goes: the start of the selection, or the cursor.
+    fn insert_point(&self) -> usize {
+        self.selection_range()
+            .map_or_else(|| self.cursor_char_idx(), |range| range.start)
+    }
 
-        self.lines[self.cursor_y] = one;
-        self.lines[self.cursor_y + 1] = two;
+    // The line break that new lines inserted at char_idx get: the style of the
+    // file, or in a file of mixed styles, the style of the cursor line or the
+    // line above it. Right after a lone CR, a LF would join it into one CRLF
+    // break, so the new line ends in a CR like the line before.
+    fn line_break(&self, char_idx: usize) -> &'static str {
+        if char_idx > 0 && self.buffer.char(char_idx - 1) == '\r' {
+            return LineEnding::Cr.as_str();
+        }
+        if self.line_ending != LineEnding::Mixed {
+            return self.line_ending.as_str();
+        }
 
-        self.move_cursor_y(1);
-        self.cursor_x = 0;
+        self.buffer
+            .line_ending(self.cursor_y)
+            .or_else(|| self.buffer.line_ending(self.cursor_y.saturating_sub(1)))
+            .unwrap_or_default()
+            .as_str()
+    }
+
+    // Gives the line breaks of text, in whatever style, the style of new lines.
+    fn convert_line_breaks(&self, text: &str, char_idx: usize) -> String {
+        let text = text.replace("\r\n", "\n").replace('\r', "\n");
+        match self.line_break(char_idx) {
+            "\n" => text,
+            line_break => text.replace('\n', line_break),
+        }
+    }
+
+    // Gives every line the same line ending style, as one undo step.
+    // The cursor stays on the same line and column.
+    pub fn convert_line_endings(&mut self, line_ending: LineEnding) {
+        if self.hex.is_some() {
+            return;
+        }
+
+        let (cursor_x, cursor_y) = (self.cursor_x, self.cursor_y);
+        self.clear_selection();
+        self.history.begin(self.cursor_char_idx());
+
+        // From the last line up, so the lines above keep their char indices.
+        for y in (0..self.buffer.len_lines()).rev() {
+            if matches!(self.buffer.line_ending(y), Some(ending) if ending != line_ending) {
+                let line_end = self.buffer.line_to_char(y) + self.buffer.line_len(y);
+                self.remove_text(line_end..line_end + self.buffer.line_ending_len(y));
+                self.insert_text(line_end, line_ending.as_str(), false);
+            }
+        }
+
+        self.history.commit();
+        self.line_ending = line_ending;
+        self.cursor_x = cursor_x;
+        self.cursor_y = cursor_y;
         self.reset_cursor();
     }
 
+    // Runs a cursor movement. With shift held the selection is extended
+    // to the new cursor position, otherwise the selection is dropped.
+    // keyflags = (BLANK, alt, control, shift) 0b0111
+    pub fn move_with_selection(&mut self, keyflags: u8, movement: impl FnOnce(&mut FileWindow)) {
+        if keyflags & 0b0001 == 0 {
+            self.clear_selection();
+            movement(self);
+            return;
+        }
+
+        if !self.selection_active {
+            self.selection_active = true;
+            self.selection_start_x = self.cursor_x;
+            self.selection_start_y = self.cursor_y;
+        }
+
+        movement(self);
+
+        self.selection_end_x = self.cursor_x;
+        self.selection_end_y = self.cursor_y;
+    }
+
+    pub fn clear_selection(&mut self) {
+        self.selection_active = false;
+    }
+
+    // The selection as ((x, y), (x, y)) grapheme positions, in document order.
+    pub fn selection_bounds(&self) -> Option<((usize, usize), (usize, usize))> {
+        if !self.selection_active {
+            return None;
+        }
+
+        let start = (self.selection_start_x, self.selection_start_y);
+        let end = (self.selection_end_x, self.selection_end_y);
+
+        match (start.1, start.0).cmp(&(end.1, end.0)) {
+            std::cmp::Ordering::Less => Some((start, end)),
+            std::cmp::Ordering::Greater => Some((end, start)),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    // The selected chars in the buffer, if anything is selected.
+    pub fn selection_range(&self) -> Option<std::ops::Range<usize>> {
+        let ((start_x, start_y), (end_x, end_y)) = self.selection_bounds()?;
+        Some(self.char_idx_at(start_x, start_y)..self.char_idx_at(end_x, end_y))
+    }
+
+    // Returns the selected text, if anything is selected.
+    pub fn selected_text(&self) -> Option<String> {
+        let range = self.selection_range()?;
+        Some(self.buffer.slice(range).to_string())
+    }
+
     pub fn move_cursor_x(&mut self, dx: isize) {
+        self.history.break_group();
         if self.cursor_x as isize + dx >= 0 {
             self.cursor_x = (self.cursor_x as isize + dx) as usize;
         }
@@ -120,132 +904,452 @@ impl FileWindow {
     /// move_cursor_y(-1) moves the cursor up one line.
     /// move_cursor_y(1) moves the cursor down one line.
     pub fn move_cursor_y(&mut self, dy: isize) {
+        self.history.break_group();
         if self.cursor_y as isize + dy >= 0 {
             self.cursor_y = (self.cursor_y as isize + dy) as usize;
         }
         self.reset_cursor();
     }
+
+    pub fn move_cursor_home(&mut self) {
+        self.history.break_group();
+        self.cursor_x = 0;
+    }
+
+    pub fn move_cursor_end(&mut self) {
+        self.history.break_group();
+        self.cursor_x = self.buffer.line_grapheme_len(self.cursor_y);
+    }
+
+    // Moves the cursor to the start of the file.
+    pub fn move_cursor_start(&mut self) {
+        self.history.break_group();
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+    }
+
+    // Moves the cursor to the end of the file.
+    pub fn move_cursor_last(&mut self) {
+        self.history.break_group();
+        self.cursor_y = self.buffer.len_lines() - 1;
+        self.cursor_x = self.buffer.line_grapheme_len(self.cursor_y);
+    }
+
+    // Page up and down scroll the view by a page,
+    // and move the cursor with it so it stays on the same screen row.
+    pub fn page_up(&mut self) {
+        let page = self.view_height.max(1);
+        self.scroll_offset = self.scroll_offset.saturating_sub(page);
+        self.move_cursor_y(-(page as isize));
+    }
+
+    pub fn page_down(&mut self) {
+        let page = self.view_height.max(1);
+        let last_line = self.buffer.len_lines() - 1;
+        self.scroll_offset = min(self.scroll_offset + page, last_line);
+        self.move_cursor_y(page as isize);
+    }
+
+    // Updates the size of the editor area and scrolls so the cursor is visible.
+    pub fn set_view_size(&mut self, width: usize, height: usize) {
+        self.view_width = width;
+        self.view_height = height;
+        self.scroll_to_cursor();
+    }
+
+    // Scrolls the view so that the cursor stays at least SCROLL_OFF
+    // lines and columns away from the edges, where the view is big enough.
+    pub fn scroll_to_cursor(&mut self) {
+        if self.view_height == 0 || self.view_width == 0 {
+            return;
+        }
+
+        let margin_y = min(SCROLL_OFF, (self.view_height - 1) / 2);
+        if self.cursor_y < self.scroll_offset + margin_y {
+            self.scroll_offset = self.cursor_y.saturating_sub(margin_y);
+        } else if self.cursor_y + margin_y >= self.scroll_offset + self.view_height {
+            self.scroll_offset = self.cursor_y + margin_y + 1 - self.view_height;
+        }
+        self.scroll_offset = min(self.scroll_offset, self.buffer.len_lines() - 1);
+
+        let cursor_x = self.cursor_display_x();
+        let margin_x = min(SCROLL_OFF, (self.view_width - 1) / 2);
+        if cursor_x < self.scroll_x + margin_x {
+            self.scroll_x = cursor_x.saturating_sub(margin_x);
+        } else if cursor_x + margin_x >= self.scroll_x + self.view_width {
+            self.scroll_x = cursor_x + margin_x + 1 - self.view_width;
+        }
+    }
+
     // reset_cursor fixes the cursor position so that
     // the position is always valid.
     // This is usefull for new lines, line deletions, and removing characters.
     // In this case, we simply reset the cursor anytime we move the cursor.
     pub fn reset_cursor(&mut self) {
-        // self.cursor_x = 0;
-        // self.cursor_y = 0;
-
-        self.cursor_y = min(self.cursor_y, self.lines.len() - 1);
-        self.cursor_x = min(self.cursor_x, self.lines[self.cursor_y].len());
+        self.cursor_y = min(self.cursor_y, self.buffer.len_lines() - 1);
+        self.cursor_x = min(self.cursor_x, self.buffer.line_grapheme_len(self.cursor_y));
     }
 
     pub fn insert_char(&mut self, c: char) {
-        let mut line = self.lines[self.cursor_y].clone();
+        // A combining mark joins the grapheme before it,
+        // so the cursor is placed after the inserted char rather than moved by one.
+        let mut utf8 = [0; 4];
+        let text = c.encode_utf8(&mut utf8);
 
-        // println!("self.cursor_x {}  -- insert_char: {}", self.cursor_x, c);
-        line.insert(self.cursor_x, c);
-        self.lines[self.cursor_y] = line;
-
-        self.reset_cursor();
-        self.move_cursor_x(1);
+        if self.selection_range().is_some() {
+            self.replace_selection(text);
+        } else {
+            self.insert_text(self.cursor_char_idx(), text, true);
+        }
     }
 
-    // delete_line removes a line from the file.
+    // backspace_line removes the line ending above the cursor.
     // If the line has text to the right of the cursor,
     // the text is moved to the above line.
     fn backspace_line(&mut self) {
-        if self.lines.len() > 1 {
-            // let x1 = self.lines[self.cursor_y - 1].len();
-
+        if self.cursor_y > 0 {
             let new_y = self.cursor_y - 1;
-            let new_x = self.lines[self.cursor_y - 1].len();
-
-            let current_line = self.lines[self.cursor_y].clone();
-            self.lines[self.cursor_y - 1] += current_line.as_str();
-
-            self.cursor_y = new_y;
-            self.cursor_x = new_x;
-            self.reset_cursor();
-
-            self.lines.remove(self.cursor_y + 1);
+            let line_end = self.buffer.line_to_char(new_y) + self.buffer.line_len(new_y);
+            let ending_len = self.buffer.line_ending_len(new_y);
+            self.remove_text(line_end..line_end + ending_len);
         }
     }
 
     fn delete_line(&mut self) {
-        if self.lines.len() > self.cursor_y + 1 {
-            let current_line = self.lines[self.cursor_y + 1].clone();
-            self.lines[self.cursor_y] += current_line.as_str();
-
-            self.reset_cursor();
-
-            self.lines.remove(self.cursor_y + 1);
+        if self.buffer.len_lines() > self.cursor_y + 1 {
+            let line_end = self.cursor_char_idx();
+            let ending_len = self.buffer.line_ending_len(self.cursor_y);
+            self.remove_text(line_end..line_end + ending_len);
         }
     }
 
     pub fn delete_char(&mut self) {
-        let mut line = self.lines[self.cursor_y].clone();
+        if self.delete_selection() {
+            return;
+        }
 
         // If the cursor is at the end of the line,
         // we need to delete the new line.
-        if self.cursor_x == line.len() {
+        if self.cursor_x == self.buffer.line_grapheme_len(self.cursor_y) {
             self.delete_line();
         } else {
-            // If the Cursur is anywhere else, we just remove the character.
-            line.remove(self.cursor_x);
-            self.lines[self.cursor_y] = line;
-
-            // self.move_cursor_x(-1);
-            self.reset_cursor();
+            // If the Cursur is anywhere else, we just remove the grapheme.
+            self.remove_text(self.grapheme_char_range(self.cursor_x));
         }
     }
 
     pub fn backspace_char(&mut self) {
-        let mut line = self.lines[self.cursor_y].clone();
+        if self.delete_selection() {
+            return;
+        }
 
         // If the cursor is at the beginning of the line,
         // we need to delete the new line.
         if self.cursor_x == 0 {
             self.backspace_line();
         } else {
-            // If the Cursur is anywhere else, we just remove the character.
-            line.remove(self.cursor_x - 1);
-            self.lines[self.cursor_y] = line;
-
-            self.move_cursor_x(-1);
-            self.reset_cursor();
+            // If the Cursur is anywhere else, we just remove the grapheme.
+            self.remove_text(self.grapheme_char_range(self.cursor_x - 1));
         }
     }
 
-    // return a slice of the lines in the file given a start and end line.
-    pub fn get_lines(&self, start: usize, mut end: usize) -> &[String] {
-        // Verify that the start and end are valid.
-        // assert!(start <= end);
-
-        end = min(self.lines.len(), end);
+    // return the lines in the file given a start and end line.
+    // Line endings are not included.
+    pub fn get_lines(&self, start: usize, mut end: usize) -> Vec<String> {
+        end = min(self.buffer.len_lines(), end);
 
-        &self.lines[start..end]
+        (min(start, end)..end)
+            .map(|y| self.buffer.line_text(y).to_string())
+            .collect()
     }
 
     // Given a line offset, return the lines that are visible.
-    pub fn get_visible_lines(&self, line_amount: usize) -> &[String] {
+    pub fn get_visible_lines(&self, line_amount: usize) -> Vec<String> {
         let start = self.scroll_offset;
         let end = start + line_amount;
         self.get_lines(start, end)
     }
 
-    pub fn get_display_text(&self) -> String {
-        let display_string: String = self
-            .get_lines(0, 8)
-            .iter()
+    // Builds the styled lines that are visible in the view.
+    // The grapheme under the cursor is drawn reversed, so wide characters
+    // and combining marks are highlighted as a whole.
+    pub fn get_display_text(&self) -> Text<'static> {
+        let cursor_style = Style::default().add_modifier(Modifier::REVERSED);
+        let selection_style = Style::default().bg(Color::Blue);
+        let selection = self.selection_bounds();
+
+        let view_start = self.scroll_x;
+        let view_end = self.scroll_x + self.view_width;
+
+        let lines = self
+            .get_visible_lines(self.view_height)
+            .into_iter()
             .enumerate()
-            .map(|(y, strs)| {
-                let mut s = strs.clone();
-                if y == self.cursor_y {
-                    s.insert(self.cursor_x, '█');
+            .map(|(row, line)| {
+                let y = self.scroll_offset + row;
+                let graphemes: Vec<&str> = line.graphemes(true).collect();
+
+                // The selected columns of this line. A column past the end of
+                // the line means the line ending is selected too.
+                let selected = match selection {
+                    Some(((start_x, start_y), (end_x, end_y))) if start_y <= y && y <= end_y => {
+                        let start = if y == start_y { start_x } else { 0 };
+                        let end = if y == end_y {
+                            end_x
+                        } else {
+                            graphemes.len() + 1
+                        };
+                        start..end
+                    }
+                    _ => 0..0,
+                };
+
+                let mut spans = Vec::new();
+                let mut column = 0;
+                for x in 0..=graphemes.len() {
+                    let is_cursor = y == self.cursor_y && x == self.cursor_x;
+
+                    // At the end of the line, or on a zero width grapheme,
+                    // the cursor is drawn as a blank cell.
+                    let text = match graphemes.get(x) {
+                        Some(g) if is_cursor && g.width() == 0 => format!(" {}", g),
+                        Some(g) => g.to_string(),
+                        None if is_cursor || selected.contains(&x) => " ".to_string(),
+                        None => continue,
+                    };
+
+                    // Only the columns inside the view are drawn. A wide grapheme
+                    // cut by the left edge is replaced by blanks.
+                    let start = column;
+                    column += text.width().max(1);
+                    if start >= view_end {
+                        break;
+                    }
+                    let text = match start < view_start {
+                        true if column <= view_start => continue,
+                        true => " ".repeat(column - view_start),
+                        false => text,
+                    };
+
+                    let style = if is_cursor {
+                        cursor_style
+                    } else if selected.contains(&x) {
+                        selection_style
+                    } else {
+                        Style::default()
+                    };
+
+                    // Neighbouring graphemes with the same style share a span.
+                    match spans.last_mut() {
+                        Some(Span {
+                            content,
+                            style: last,
+                        }) if *last == style => content.to_mut().push_str(&text),
+                        _ => spans.push(Span::styled(text, style)),
+                    }
                 }
-                s
+
+                Spans::from(spans)
             })
-            .collect::<Vec<String>>()
-            .join("\n");
+            .collect::<Vec<Spans>>();
+
+        Text::from(lines)
+    }
+}
+
+// The name of the file at a path, for its tab.
+fn file_name(path: &Path) -> Result<OsString, Error> {
+    path.file_name()
+        .map(|name| name.to_os_string())
+        .ok_or_else(|| Error::NotAFile(path.to_path_buf()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn file(text: &str) -> FileWindow {
+        FileWindow::from_reader("notes.txt", text.as_bytes()).unwrap()
+    }
+
+    fn press(file: &mut FileWindow, keycode: KeyCode, keyflags: u8) {
+        file.handle_keyboard_input(keycode, keyflags);
+    }
+
+    fn type_text(file: &mut FileWindow, text: &str) {
+        for c in text.chars() {
+            press(file, KeyCode::Char(c), 0b0000);
+        }
+    }
+
+    #[test]
+    fn shift_moves_extend_the_selection() {
+        let mut file = file("hello\nworld\n");
+        press(&mut file, KeyCode::Right, 0b0001);
+        press(&mut file, KeyCode::Right, 0b0001);
+        assert_eq!(file.selected_text().as_deref(), Some("he"));
+
+        press(&mut file, KeyCode::Down, 0b0001);
+        assert_eq!(file.selected_text().as_deref(), Some("hello\nwo"));
+
+        // Moving back past the anchor selects the other way.
+        press(&mut file, KeyCode::Up, 0b0001);
+        press(&mut file, KeyCode::Home, 0b0001);
+        assert_eq!(file.selected_text(), None);
+    }
+
+    #[test]
+    fn moving_without_shift_drops_the_selection() {
+        let mut file = file("hello");
+        press(&mut file, KeyCode::Right, 0b0001);
+        press(&mut file, KeyCode::Right, 0b0000);
+        press(&mut file, KeyCode::Right, 0b0001);
+        assert_eq!(file.selected_text().as_deref(), Some("l"));
+    }
+
+    #[test]
+    fn typing_replaces_the_selection() {
+        let mut file = file("hello");
+        press(&mut file, KeyCode::End, 0b0001);
+        type_text(&mut file, "bye");
+        assert_eq!(file.buffer.to_string(), "bye");
+        assert_eq!(file.selected_text(), None);
+
+        // The replacement is one step, the typing after it another.
+        file.undo();
+        assert_eq!(file.buffer.to_string(), "b");
+        file.undo();
+        assert_eq!(file.buffer.to_string(), "hello");
+    }
+
+    #[test]
+    fn typing_drops_an_empty_selection() {
+        let mut file = file("hello");
+        press(&mut file, KeyCode::Right, 0b0001);
+        press(&mut file, KeyCode::Left, 0b0001);
+        type_text(&mut file, "XY");
+        // The selection starts at the cursor, not where the empty one was.
+        press(&mut file, KeyCode::End, 0b0001);
+        assert_eq!(file.selected_text().as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn alt_r_picks_the_register_of_the_next_copy_and_paste() {
+        let mut file = file("hello");
+        press(&mut file, KeyCode::Right, 0b0001);
+        press(&mut file, KeyCode::Right, 0b0001);
+        press(&mut file, KeyCode::Char('r'), 0b0100);
+        press(&mut file, KeyCode::Char('a'), 0b0000);
+        press(&mut file, KeyCode::Char('c'), 0b0010);
+
+        press(&mut file, KeyCode::End, 0b0001);
+        press(&mut file, KeyCode::Char('c'), 0b0010);
+
+        press(&mut file, KeyCode::End, 0b0000);
+        press(&mut file, KeyCode::Char('r'), 0b0100);
+        press(&mut file, KeyCode::Char('a'), 0b0000);
+        press(&mut file, KeyCode::Char('v'), 0b0010);
+        press(&mut file, KeyCode::Char('v'), 0b0010);
+        // "he" from register a, then "hello" from the unnamed register.
+        assert_eq!(file.buffer.to_string(), "hellohehello");
+    }
+
+    #[test]
+    fn converts_line_endings_as_one_undo_step() {
+        let mut file = file("a\r\nb\nc\rd");
+        assert_eq!(file.line_ending, LineEnding::Mixed);
+
+        file.convert_line_endings(LineEnding::CrLf);
+        assert_eq!(file.buffer.to_string(), "a\r\nb\r\nc\r\nd");
+        assert_eq!(file.line_ending, LineEnding::CrLf);
+        assert!(file.is_modified());
+
+        file.undo();
+        assert_eq!(file.buffer.to_string(), "a\r\nb\nc\rd");
+        assert_eq!(file.line_ending, LineEnding::Mixed);
+        assert!(!file.is_modified());
+
+        file.redo();
+        assert_eq!(file.line_ending, LineEnding::CrLf);
+    }
+
+    #[test]
+    fn undoing_a_conversion_restores_the_line_ending() {
+        let mut file = file("a\nb\n");
+        file.convert_line_endings(LineEnding::CrLf);
+        file.undo();
+
+        assert_eq!(file.buffer.to_string(), "a\nb\n");
+        assert_eq!(file.line_ending, LineEnding::Lf);
+    }
+
+    #[test]
+    fn new_lines_after_a_lone_cr_stay_separate_lines() {
+        let mut typed = file("a\rb\nc");
+        typed.go_to(2, 1);
+        typed.insert_new_line();
+        assert_eq!(typed.buffer.to_string(), "a\r\rb\nc");
+        assert_eq!(typed.buffer.len_lines(), 4);
+
+        let mut pasted = file("a\rb\nc");
+        pasted.go_to(2, 1);
+        pasted.paste_text("x\ny\n");
+        assert_eq!(pasted.buffer.to_string(), "a\rx\ry\rb\nc");
+    }
+
+    #[test]
+    fn converting_to_the_same_line_ending_changes_nothing() {
+        let mut file = file("a\nb\n");
+        file.convert_line_endings(LineEnding::Lf);
+
+        assert_eq!(file.buffer.to_string(), "a\nb\n");
+        assert!(!file.is_modified());
+    }
+
+    #[test]
+    fn reloading_appended_text_is_one_small_undo_step() {
+        let mut file = file("one\ntwo\n");
+        type_text(&mut file, "x");
+        press(&mut file, KeyCode::Backspace, 0b0000);
+        file.saved_revision = file.history.revision();
+        file.go_to(2, 3);
+
+        file.reload(b"one\ntwo\nthree\n".to_vec(), None);
+        assert_eq!(file.buffer.to_string(), "one\ntwo\nthree\n");
+        assert!(!file.is_modified());
+        assert_eq!((file.cursor_y, file.cursor_x), (1, 2));
+        let reloaded = file.history.undo_stack.last().unwrap();
+        assert_eq!(reloaded.edits, [Edit::insert(8, "three\n")]);
+
+        // Undoing the reload gets back the text from before, which differs from the file.
+        file.undo();
+        assert_eq!(file.buffer.to_string(), "one\ntwo\n");
+        assert!(file.is_modified());
+        file.redo();
+        assert!(!file.is_modified());
+    }
+
+    #[test]
+    fn reloading_replaces_only_the_changed_middle() {
+        let mut file = file("one\ntwo\nthree\n");
+        file.reload(b"one\nTWO\nthree\n".to_vec(), None);
+        assert_eq!(file.buffer.to_string(), "one\nTWO\nthree\n");
+        let reloaded = file.history.undo_stack.last().unwrap();
+        assert_eq!(reloaded.edits.len(), 2);
+        assert_eq!(
+            (reloaded.edits[0].char_idx, reloaded.edits[0].len_chars()),
+            (4, 3)
+        );
+        assert_eq!(reloaded.edits[1], Edit::insert(4, "TWO"));
+
+        // Unchanged contents record nothing.
+        let undo_steps = file.history.undo_stack.len();
+        file.reload(b"one\nTWO\nthree\n".to_vec(), None);
+        assert_eq!(file.history.undo_stack.len(), undo_steps);
+        assert!(!file.is_modified());
 
-        display_string
+        file.reload(b"one\r\nthree\r\n".to_vec(), None);
+        assert_eq!(file.buffer.to_string(), "one\r\nthree\r\n");
+        assert_eq!(file.line_ending, LineEnding::CrLf);
     }
 }
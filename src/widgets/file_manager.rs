@@ -1,11 +1,31 @@
+use std::cell::RefCell;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
 use crossterm::event::KeyCode;
 
+use crate::clipboard::Clipboard;
+use crate::error::Error;
+use crate::save::Backup;
+use crate::swap::SwapWriter;
+
 use crate::widgets::file::FileWindow;
 use crate::widgets::Window;
 
 pub struct FileManagerWindow {
     pub current_file_index: usize,
     pub file_list: Vec<FileWindow>,
+    // Files added to the manager keep their undo history between sessions.
+    pub persistent_history: bool,
+    // Files added to the manager keep their previous version on save like this.
+    pub backup: Backup,
+    // With --stdout, the stdin buffer, or else the file current on quit,
+    // is written to stdout on exit.
+    pub stdout: bool,
+    // The clipboard is shared with every file in the manager.
+    pub clipboard: Rc<RefCell<Clipboard>>,
+    // So is the writer of their swap files.
+    pub swap_writer: Rc<SwapWriter>,
 }
 
 impl Window for FileManagerWindow {
@@ -22,16 +42,93 @@ impl Window for FileManagerWindow {
 
 impl FileManagerWindow {
     pub fn new() -> FileManagerWindow {
-        FileManagerWindow {
+        let mut file_manager = FileManagerWindow {
             current_file_index: 0,
-            file_list: vec![FileWindow::new("HI"), FileWindow::new("Hellooo!")],
-        }
+            file_list: Vec::new(),
+            persistent_history: false,
+            backup: Backup::default(),
+            stdout: false,
+            clipboard: Rc::new(RefCell::new(Clipboard::default())),
+            swap_writer: Rc::new(SwapWriter::default()),
+        };
+
+        file_manager.add_file_menu(FileWindow::new("HI"));
+        file_manager.add_file_menu(FileWindow::new("Hellooo!"));
+        file_manager
     }
 
-    pub fn add_file_menu(&mut self, file: FileWindow) {
+    pub fn add_file_menu(&mut self, mut file: FileWindow) {
+        file.persistent_history |= self.persistent_history;
+        file.backup = self.backup.clone();
+        file.clipboard = Rc::clone(&self.clipboard);
+        file.swap_writer = Rc::clone(&self.swap_writer);
         self.file_list.push(file);
     }
 
+    // Opens a file in a new tab and selects it.
+    // A file that is already open is selected instead of being opened twice.
+    pub fn open_file(&mut self, path: Box<Path>) -> Result<(), Error> {
+        let canonical = path.canonicalize().ok();
+        let open_index = self.file_list.iter().position(|file| {
+            let file_path = file.path.as_ref().and_then(|p| p.canonicalize().ok());
+            file_path.is_some() && file_path == canonical
+        });
+
+        self.current_file_index = match open_index {
+            Some(index) => index,
+            None => {
+                self.add_file_menu(FileWindow::open_file(path)?);
+                self.file_list.len() - 1
+            }
+        };
+        Ok(())
+    }
+
+    // Closing the last file leaves an empty, unnamed file open.
+    pub fn close_file(&mut self, index: usize) {
+        self.file_list.remove(index).remove_swap();
+
+        if self.file_list.is_empty() {
+            self.add_file_menu(FileWindow::new("untitled"));
+        }
+
+        if index < self.current_file_index || self.current_file_index >= self.file_list.len() {
+            self.current_file_index -= 1;
+        }
+    }
+
+    // Writes a copy of every modified file next to it, as "name.save", when the
+    // editor is about to be killed. The files themselves are left alone.
+    // Returns where each copy went, or why it could not be written.
+    pub fn emergency_save(&self) -> Vec<Result<PathBuf, Error>> {
+        self.file_list
+            .iter()
+            .filter(|file| file.is_modified())
+            .map(|file| {
+                let base = match &file.path {
+                    Some(path) => path.to_path_buf(),
+                    None => std::env::current_dir()?.join(&file.name),
+                };
+                file.write_copy(&base)
+            })
+            .collect()
+    }
+
+    // Writes the swap files of the modified files, called on every tick.
+    pub fn write_swaps(&mut self) {
+        for file in self.file_list.iter_mut() {
+            file.write_swap();
+        }
+    }
+
+    // Quitting normally leaves no swap files behind, as the user chose
+    // to save or discard every change.
+    pub fn remove_swaps(&mut self) {
+        for file in self.file_list.iter_mut() {
+            file.remove_swap();
+        }
+    }
+
     pub fn next(&mut self) {
         self.current_file_index = (self.current_file_index + 1) % self.file_list.len();
     }
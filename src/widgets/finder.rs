@@ -0,0 +1,194 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+
+use crossterm::event::KeyCode;
+
+use super::Window;
+
+// Walking stops after this many files, so huge directories stay responsive.
+const MAX_FILES: usize = 20_000;
+// The number of lines read from the highlighted file for the preview.
+const PREVIEW_LINES: usize = 100;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FinderResult {
+    Open(PathBuf),
+    Cancelled,
+}
+
+// A file that matches the query, with the char positions of the matched
+// chars in its path so they can be highlighted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FinderMatch {
+    pub path: PathBuf,
+    pub score: i64,
+    pub positions: Vec<usize>,
+}
+
+/// FinderWindow picks a file in the project directory by fuzzy matching its path.
+pub struct FinderWindow {
+    pub root: PathBuf,
+    pub query: String,
+    pub matches: Vec<FinderMatch>,
+    pub selected: usize,
+    // The first lines of the selected file.
+    pub preview: Vec<String>,
+    pub result: Option<FinderResult>,
+
+    // Every file under root, relative to it.
+    files: Vec<PathBuf>,
+}
+
+impl Window for FinderWindow {
+    // keyflags = (BLANK, alt, control, shift) 0b0111
+    fn handle_keyboard_input(&mut self, keycode: KeyCode, keyflags: u8) {
+        match (keycode, keyflags) {
+            (KeyCode::Char(c), 0b0000 | 0b0001) => {
+                self.query.push(c);
+                self.update_matches();
+            }
+            (KeyCode::Backspace, _) => {
+                self.query.pop();
+                self.update_matches();
+            }
+
+            (KeyCode::Up, _) => self.select(self.selected.saturating_sub(1)),
+            (KeyCode::Down, _) => self.select(self.selected + 1),
+            (KeyCode::PageUp, _) => self.select(self.selected.saturating_sub(10)),
+            (KeyCode::PageDown, _) => self.select(self.selected + 10),
+
+            (KeyCode::Enter, _) => {
+                if let Some(selected) = self.matches.get(self.selected) {
+                    self.result = Some(FinderResult::Open(self.root.join(&selected.path)));
+                }
+            }
+            (KeyCode::Esc, _) => self.result = Some(FinderResult::Cancelled),
+
+            _ => (),
+        }
+    }
+}
+
+impl FinderWindow {
+    // Walks the files under root, skipping hidden files and those
+    // ignored by .gitignore and similar files.
+    pub fn new(root: PathBuf) -> FinderWindow {
+        let files = ignore::WalkBuilder::new(&root)
+            .build()
+            .filter_map(Result::ok)
+            .filter(|entry| entry.file_type().is_some_and(|t| t.is_file()))
+            .filter_map(|entry| entry.path().strip_prefix(&root).ok().map(Path::to_path_buf))
+            .take(MAX_FILES)
+            .collect();
+
+        let mut finder = FinderWindow {
+            root,
+            query: String::new(),
+            matches: Vec::new(),
+            selected: 0,
+            preview: Vec::new(),
+            result: None,
+            files,
+        };
+        finder.update_matches();
+        finder
+    }
+
+    // Scores every file against the query, best matches first.
+    // Equal scores keep shorter paths first, then sort by name.
+    pub fn update_matches(&mut self) {
+        let query = &self.query;
+        self.matches = self
+            .files
+            .iter()
+            .filter_map(|path| {
+                let (score, positions) = fuzzy_match(query, &path.to_string_lossy())?;
+                Some(FinderMatch {
+                    path: path.clone(),
+                    score,
+                    positions,
+                })
+            })
+            .collect();
+
+        self.matches.sort_by(|a, b| {
+            b.score
+                .cmp(&a.score)
+                .then(a.path.as_os_str().len().cmp(&b.path.as_os_str().len()))
+                .then(a.path.cmp(&b.path))
+        });
+        self.select(0);
+    }
+
+    pub fn select(&mut self, index: usize) {
+        self.selected = index.min(self.matches.len().saturating_sub(1));
+        self.preview = match self.matches.get(self.selected) {
+            Some(selected) => read_preview(&self.root.join(&selected.path)),
+            None => Vec::new(),
+        };
+    }
+}
+
+fn read_preview(path: &Path) -> Vec<String> {
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(error) => return vec![error.to_string()],
+    };
+
+    let mut lines = Vec::new();
+    for line in BufReader::new(file).split(b'\n').take(PREVIEW_LINES) {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break,
+        };
+        if line.contains(&0) {
+            return vec!["(binary file)".to_string()];
+        }
+        lines.push(String::from_utf8_lossy(&line).trim_end().to_string());
+    }
+    lines
+}
+
+/// Matches the chars of `query` in order, ignoring case, anywhere in `candidate`.
+/// Returns a score, higher being better, and the char positions that matched.
+///
+/// Matches score more when they follow each other, when they start a word
+/// and when they are in the file name rather than its directories.
+pub fn fuzzy_match(query: &str, candidate: &str) -> Option<(i64, Vec<usize>)> {
+    let chars: Vec<char> = candidate.chars().collect();
+    let file_name_start = chars
+        .iter()
+        .rposition(|c| std::path::is_separator(*c))
+        .map_or(0, |i| i + 1);
+
+    let mut positions = Vec::new();
+    let mut score = 0;
+    let mut next = 0;
+
+    for q in query.chars().filter(|c| !c.is_whitespace()) {
+        let found = (next..chars.len()).find(|i| chars_match(q, chars[*i]))?;
+
+        score += 1;
+        if found > 0 && positions.last() == Some(&(found - 1)) {
+            score += 5;
+        }
+        if found == 0 || !chars[found - 1].is_alphanumeric() {
+            score += 3;
+        }
+        if found >= file_name_start {
+            score += 2;
+        }
+        // Skipped chars cost a little, so tighter matches win.
+        score -= (found - next).min(10) as i64 / 3;
+
+        positions.push(found);
+        next = found + 1;
+    }
+
+    Some((score, positions))
+}
+
+fn chars_match(query: char, c: char) -> bool {
+    query.to_lowercase().eq(c.to_lowercase())
+}
@@ -0,0 +1,257 @@
+use std::collections::BTreeMap;
+
+use crossterm::event::KeyCode;
+use tui::style::{Color, Modifier, Style};
+use tui::text::{Span, Spans, Text};
+
+use crate::widgets::Window;
+
+// The number of bytes shown on each row.
+pub const BYTES_PER_ROW: usize = 16;
+
+// The half of the view that typing goes to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum HexPane {
+    #[default]
+    Hex,
+    Ascii,
+}
+
+/// HexWindow shows the bytes of a binary file as rows of an offset,
+/// the bytes in hex, and the bytes as ASCII, and lets them be overwritten.
+/// Bytes are never inserted or removed, so the file keeps its size.
+#[derive(Clone, Default, Debug)]
+pub struct HexWindow {
+    pub bytes: Vec<u8>,
+    // cursor is a byte offset.
+    pub cursor: usize,
+    pub pane: HexPane,
+    // Whether the next hex digit typed goes into the low half of the byte.
+    pub low_nibble: bool,
+
+    // The bytes changed since the file was saved, with what they were then.
+    // A byte changed back to what it was is no longer listed.
+    pub changed: BTreeMap<usize, u8>,
+    // Every edit as the offset and the byte that was there before it.
+    undo_stack: Vec<(usize, u8)>,
+
+    // The first visible row, and the number of rows the view has.
+    pub scroll_row: usize,
+    pub view_height: usize,
+}
+
+/// The bytes as text, a row per line, to compare bytes with a line diff.
+pub fn dump(bytes: &[u8]) -> String {
+    let mut text = String::new();
+    for (row, chunk) in bytes.chunks(BYTES_PER_ROW).enumerate() {
+        text.push_str(&format!("{:08x} ", row * BYTES_PER_ROW));
+        for byte in chunk {
+            text.push_str(&format!(" {:02x}", byte));
+        }
+        text.push('\n');
+    }
+    text
+}
+
+impl Window for HexWindow {
+    // keyflags = (BLANK, alt, control, shift) 0b0111
+    fn handle_keyboard_input(&mut self, keycode: KeyCode, keyflags: u8) {
+        match (keycode, keyflags) {
+            (KeyCode::Char('z'), 0b0010) => self.undo(),
+
+            (KeyCode::Tab, _) => {
+                self.pane = match self.pane {
+                    HexPane::Hex => HexPane::Ascii,
+                    HexPane::Ascii => HexPane::Hex,
+                };
+                self.low_nibble = false;
+            }
+
+            (KeyCode::Char(c), 0b0000 | 0b0001) => self.type_char(c),
+
+            (KeyCode::Left, _) => self.move_cursor(-1),
+            (KeyCode::Right, _) => self.move_cursor(1),
+            (KeyCode::Up, _) => self.move_cursor(-(BYTES_PER_ROW as isize)),
+            (KeyCode::Down, _) => self.move_cursor(BYTES_PER_ROW as isize),
+            (KeyCode::PageUp, _) => self.move_cursor(-(self.page_bytes() as isize)),
+            (KeyCode::PageDown, _) => self.move_cursor(self.page_bytes() as isize),
+            (KeyCode::Home, flags) if flags & 0b0010 != 0 => self.set_cursor(0),
+            (KeyCode::End, flags) if flags & 0b0010 != 0 => self.set_cursor(usize::MAX),
+            (KeyCode::Home, _) => self.set_cursor(self.cursor - self.cursor % BYTES_PER_ROW),
+            (KeyCode::End, _) => {
+                self.set_cursor(self.cursor - self.cursor % BYTES_PER_ROW + BYTES_PER_ROW - 1)
+            }
+
+            _ => (),
+        }
+    }
+}
+
+impl HexWindow {
+    pub fn new(bytes: Vec<u8>) -> HexWindow {
+        HexWindow {
+            bytes,
+            ..Default::default()
+        }
+    }
+
+    pub fn is_modified(&self) -> bool {
+        !self.changed.is_empty()
+    }
+
+    // The bytes on disk are now the bytes shown.
+    pub fn mark_saved(&mut self) {
+        self.changed.clear();
+        self.undo_stack.clear();
+    }
+
+    fn page_bytes(&self) -> usize {
+        self.view_height.max(1) * BYTES_PER_ROW
+    }
+
+    fn move_cursor(&mut self, delta: isize) {
+        self.set_cursor(self.cursor.saturating_add_signed(delta));
+    }
+
+    // Moves the cursor to a byte, clamped to the last byte.
+    pub fn set_cursor(&mut self, offset: usize) {
+        self.cursor = offset.min(self.bytes.len().saturating_sub(1));
+        self.low_nibble = false;
+        self.scroll_to_cursor();
+    }
+
+    pub fn set_view_height(&mut self, height: usize) {
+        self.view_height = height;
+        self.scroll_to_cursor();
+    }
+
+    fn scroll_to_cursor(&mut self) {
+        let row = self.cursor / BYTES_PER_ROW;
+        if row < self.scroll_row {
+            self.scroll_row = row;
+        } else if self.view_height > 0 && row >= self.scroll_row + self.view_height {
+            self.scroll_row = row + 1 - self.view_height;
+        }
+    }
+
+    // In the hex pane two hex digits make a byte, the high half first.
+    // In the ASCII pane a printable ASCII char is a byte.
+    fn type_char(&mut self, c: char) {
+        if self.cursor >= self.bytes.len() {
+            return;
+        }
+
+        match self.pane {
+            HexPane::Hex => {
+                let digit = match c.to_digit(16) {
+                    Some(digit) => digit as u8,
+                    None => return,
+                };
+                let byte = self.bytes[self.cursor];
+                if self.low_nibble {
+                    self.overwrite(self.cursor, byte & 0xf0 | digit);
+                    self.move_cursor(1);
+                } else {
+                    self.overwrite(self.cursor, digit << 4 | byte & 0x0f);
+                    self.low_nibble = true;
+                }
+            }
+            HexPane::Ascii if c.is_ascii() && !c.is_ascii_control() => {
+                self.overwrite(self.cursor, c as u8);
+                self.move_cursor(1);
+            }
+            HexPane::Ascii => {}
+        }
+    }
+
+    fn overwrite(&mut self, offset: usize, byte: u8) {
+        let previous = self.bytes[offset];
+        if previous == byte {
+            return;
+        }
+        self.undo_stack.push((offset, previous));
+        self.set_byte(offset, byte);
+    }
+
+    fn set_byte(&mut self, offset: usize, byte: u8) {
+        let original = *self.changed.entry(offset).or_insert(self.bytes[offset]);
+        if original == byte {
+            self.changed.remove(&offset);
+        }
+        self.bytes[offset] = byte;
+    }
+
+    pub fn undo(&mut self) {
+        if let Some((offset, byte)) = self.undo_stack.pop() {
+            self.set_byte(offset, byte);
+            self.set_cursor(offset);
+        }
+    }
+
+    // The visible rows. The cursor is reversed in the pane that is typed in
+    // and underlined in the other one, and changed bytes are yellow.
+    pub fn get_display_text(&self) -> Text<'static> {
+        let rows = self.bytes.len().div_ceil(BYTES_PER_ROW).max(1);
+        let end_row = rows.min(self.scroll_row + self.view_height.max(1));
+
+        let lines: Vec<Spans> = (self.scroll_row..end_row)
+            .map(|row| {
+                let start = row * BYTES_PER_ROW;
+                let end = (start + BYTES_PER_ROW).min(self.bytes.len());
+
+                let mut spans = vec![Span::styled(
+                    format!("{:08x}  ", start),
+                    Style::default().fg(Color::DarkGray),
+                )];
+
+                for offset in start..start + BYTES_PER_ROW {
+                    if offset < end {
+                        let style = self.byte_style(offset, HexPane::Hex);
+                        spans.push(Span::styled(format!("{:02x}", self.bytes[offset]), style));
+                    } else {
+                        spans.push(Span::raw("  "));
+                    }
+                    // The two halves of a row are set apart.
+                    let gap = if offset % BYTES_PER_ROW == BYTES_PER_ROW / 2 - 1 {
+                        "  "
+                    } else {
+                        " "
+                    };
+                    spans.push(Span::raw(gap));
+                }
+
+                spans.push(Span::styled(" │", Style::default().fg(Color::DarkGray)));
+                for offset in start..end {
+                    let byte = self.bytes[offset];
+                    let c = match byte.is_ascii_graphic() || byte == b' ' {
+                        true => byte as char,
+                        false => '.',
+                    };
+                    spans.push(Span::styled(
+                        c.to_string(),
+                        self.byte_style(offset, HexPane::Ascii),
+                    ));
+                }
+                spans.push(Span::styled("│", Style::default().fg(Color::DarkGray)));
+
+                Spans::from(spans)
+            })
+            .collect();
+
+        Text::from(lines)
+    }
+
+    fn byte_style(&self, offset: usize, pane: HexPane) -> Style {
+        let mut style = Style::default();
+        if self.changed.contains_key(&offset) {
+            style = style.fg(Color::Yellow);
+        }
+        if offset == self.cursor {
+            style = match pane == self.pane {
+                true => style.add_modifier(Modifier::REVERSED),
+                false => style.add_modifier(Modifier::UNDERLINED),
+            };
+        }
+        style
+    }
+}
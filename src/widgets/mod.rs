@@ -1,9 +1,19 @@
 use crossterm::event::KeyCode;
 
+pub mod changed;
+pub mod confirm;
+pub mod diff_dialog;
+pub mod explorer;
 pub mod file;
 pub mod file_manager;
+pub mod finder;
+pub mod hex;
 pub mod navigation;
+pub mod prompt;
+pub mod recover;
 
+// Dialogs, prompts and the finder keep what the user picked in their `choice`
+// or `result`, which the App reads after every key press and acts on.
 pub trait Window {
     fn handle_keyboard_input(&mut self, keycode: KeyCode, keyflags: u8);
     // fn draw(&mut self, frame: &mut tui::Frame<impl tui::backend::Backend>, area: tui::layout::Rect);
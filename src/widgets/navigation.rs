@@ -5,6 +5,7 @@ use strum::*;
 use strum_macros::Display;
 use strum_macros::*;
 
+use super::explorer::ExplorerWindow;
 use super::Window;
 
 #[derive(Debug, Display, Clone, EnumString, EnumIter, EnumCount, PartialEq, Eq, Hash)]
@@ -18,18 +19,49 @@ pub enum ActionMenuWindow {
     SaveFileAs,
     SaveAll,
     CloseFile,
+    ConvertLineEndings,
+    ReopenWithEncoding,
+    SaveWithEncoding,
+    ToggleHexView,
+}
+
+// The part of the navigation panel that receives key presses.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NavigationFocus {
+    Menu,
+    Explorer,
 }
 
 pub struct NavigationWindow {
     pub list_state: tui::widgets::ListState,
     // pub items: MenuAction, // This is currently not important.
+
+    // The action picked with Enter. The App takes it and runs it.
+    pub pending_action: Option<ActionMenuWindow>,
+
+    // The file tree below the menu. Tab moves the focus between them.
+    pub explorer: ExplorerWindow,
+    pub focus: NavigationFocus,
 }
 
 impl Window for NavigationWindow {
-    fn handle_keyboard_input(&mut self, keycode: crossterm::event::KeyCode, _keyflags: u8) {
+    fn handle_keyboard_input(&mut self, keycode: crossterm::event::KeyCode, keyflags: u8) {
+        if keycode == KeyCode::Tab {
+            self.focus = match self.focus {
+                NavigationFocus::Menu => NavigationFocus::Explorer,
+                NavigationFocus::Explorer => NavigationFocus::Menu,
+            };
+            return;
+        }
+
+        if self.focus == NavigationFocus::Explorer {
+            self.explorer.handle_keyboard_input(keycode, keyflags);
+            return;
+        }
+
         match keycode {
             // KeyCode::Char(c) => self.insert_char(c),
-            KeyCode::Enter => self.run(),
+            KeyCode::Enter => self.pending_action = self.run(),
             // KeyCode::Backspace => self.backspace_char(),
             KeyCode::Delete => self.unselect(),
 
@@ -45,8 +77,14 @@ impl Window for NavigationWindow {
 
 impl NavigationWindow {
     pub fn new() -> NavigationWindow {
+        // The explorer starts in the directory the editor was started in.
+        let root = std::env::current_dir().unwrap_or_else(|_| std::path::PathBuf::from("."));
+
         NavigationWindow {
             list_state: ListState::default(),
+            pending_action: None,
+            explorer: ExplorerWindow::new(root),
+            focus: NavigationFocus::Menu,
         }
     }
 
@@ -69,8 +107,7 @@ impl NavigationWindow {
             0
         };
 
-        let wrapped_index =
-            Some((new_index as isize).rem_euclid(ActionMenuWindow::COUNT as isize) as usize);
+        let wrapped_index = Some(new_index.rem_euclid(ActionMenuWindow::COUNT as isize) as usize);
 
         self.list_state.select(wrapped_index);
     }
@@ -79,17 +116,11 @@ impl NavigationWindow {
         self.list_state.select(None);
     }
 
-    // This function preforms the action that is selected.
-    pub fn run(&self) {
-        if let Some(i) = self.list_state.selected() {
-            match ActionMenuWindow::iter().nth(i).unwrap() {
-                ActionMenuWindow::NewFile => println!("New File"),
-                ActionMenuWindow::OpenFile => println!("Open File"),
-                ActionMenuWindow::SaveFile => println!("Save File"),
-                ActionMenuWindow::SaveFileAs => println!("Save File As"),
-                ActionMenuWindow::SaveAll => println!("Save All"),
-                ActionMenuWindow::CloseFile => println!("Close File"),
-            }
-        }
+    // This function returns the action that is selected.
+    // The actions work on the open files, so they are performed by the App.
+    pub fn run(&self) -> Option<ActionMenuWindow> {
+        self.list_state
+            .selected()
+            .and_then(|i| ActionMenuWindow::iter().nth(i))
     }
 }
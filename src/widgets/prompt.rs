@@ -0,0 +1,231 @@
+use std::path::{PathBuf, MAIN_SEPARATOR};
+
+use crossterm::event::KeyCode;
+
+use super::confirm::PendingAction;
+use super::Window;
+
+// What the text typed into a prompt is used for.
+// Each kind keeps its own history of submitted inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PromptKind {
+    OpenFile,
+    SaveFileAs,
+    GoToLine,
+    Find,
+    NewFile,
+    NewFolder,
+    Rename,
+    Delete,
+    LineEnding,
+    ReopenWithEncoding,
+    SaveWithEncoding,
+}
+
+impl PromptKind {
+    // Tab completes file system paths in the prompts that ask for one.
+    pub fn completes_paths(&self) -> bool {
+        matches!(
+            self,
+            PromptKind::OpenFile
+                | PromptKind::SaveFileAs
+                | PromptKind::NewFile
+                | PromptKind::NewFolder
+                | PromptKind::Rename
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PromptResult {
+    Submitted(String),
+    Cancelled,
+}
+
+/// PromptWindow is a single line text input, drawn in place of the status bar.
+pub struct PromptWindow {
+    pub kind: PromptKind,
+    pub label: String,
+    pub input: String,
+    // cursor is a char index into the input.
+    pub cursor: usize,
+    pub result: Option<PromptResult>,
+    // The file or directory the prompt is about, such as the file being renamed.
+    pub target: Option<PathBuf>,
+    // The open file the prompt acts on, the current one when it was opened.
+    pub file_index: usize,
+    // The action that goes on once the prompt is done, such as quitting
+    // after an unnamed file is saved.
+    pub pending_action: Option<PendingAction>,
+
+    // Earlier inputs of the same kind, oldest first.
+    // Up and Down walk through them, history_index being the one shown.
+    history: Vec<String>,
+    history_index: Option<usize>,
+    // The input that was typed before walking the history, restored after it.
+    draft: String,
+}
+
+impl Window for PromptWindow {
+    // keyflags = (BLANK, alt, control, shift) 0b0111
+    fn handle_keyboard_input(&mut self, keycode: KeyCode, keyflags: u8) {
+        match (keycode, keyflags) {
+            (KeyCode::Char('u'), 0b0010) => self.set_input(String::new()),
+            (KeyCode::Char(c), 0b0000 | 0b0001) => self.insert_char(c),
+            (KeyCode::Backspace, _) => self.backspace_char(),
+            (KeyCode::Delete, _) => self.delete_char(),
+
+            (KeyCode::Left, _) => self.cursor = self.cursor.saturating_sub(1),
+            (KeyCode::Right, _) => self.cursor = (self.cursor + 1).min(self.len_chars()),
+            (KeyCode::Home, _) => self.cursor = 0,
+            (KeyCode::End, _) => self.cursor = self.len_chars(),
+
+            (KeyCode::Up, _) => self.history_previous(),
+            (KeyCode::Down, _) => self.history_next(),
+            (KeyCode::Tab, _) if self.kind.completes_paths() => self.complete_path(),
+
+            (KeyCode::Enter, _) => self.result = Some(PromptResult::Submitted(self.input.clone())),
+            (KeyCode::Esc, _) => self.result = Some(PromptResult::Cancelled),
+
+            _ => (),
+        }
+    }
+}
+
+impl PromptWindow {
+    pub fn new(kind: PromptKind, label: &str, input: &str, history: Vec<String>) -> PromptWindow {
+        PromptWindow {
+            kind,
+            label: label.to_string(),
+            input: input.to_string(),
+            cursor: input.chars().count(),
+            result: None,
+            target: None,
+            file_index: 0,
+            pending_action: None,
+            history,
+            history_index: None,
+            draft: String::new(),
+        }
+    }
+
+    fn len_chars(&self) -> usize {
+        self.input.chars().count()
+    }
+
+    // The byte index in the input of a char index.
+    fn byte_idx(&self, char_idx: usize) -> usize {
+        self.input
+            .char_indices()
+            .nth(char_idx)
+            .map_or(self.input.len(), |(i, _)| i)
+    }
+
+    // Replaces the input and moves the cursor to its end.
+    pub fn set_input(&mut self, input: String) {
+        self.cursor = input.chars().count();
+        self.input = input;
+    }
+
+    pub fn insert_char(&mut self, c: char) {
+        let i = self.byte_idx(self.cursor);
+        self.input.insert(i, c);
+        self.cursor += 1;
+    }
+
+    pub fn backspace_char(&mut self) {
+        if self.cursor > 0 {
+            self.cursor -= 1;
+            self.delete_char();
+        }
+    }
+
+    pub fn delete_char(&mut self) {
+        if self.cursor < self.len_chars() {
+            let i = self.byte_idx(self.cursor);
+            self.input.remove(i);
+        }
+    }
+
+    pub fn history_previous(&mut self) {
+        let index = match self.history_index {
+            None if self.history.is_empty() => return,
+            None => {
+                self.draft = self.input.clone();
+                self.history.len() - 1
+            }
+            Some(index) => index.saturating_sub(1),
+        };
+
+        self.history_index = Some(index);
+        self.set_input(self.history[index].clone());
+    }
+
+    pub fn history_next(&mut self) {
+        match self.history_index {
+            None => (),
+            Some(index) if index + 1 < self.history.len() => {
+                self.history_index = Some(index + 1);
+                self.set_input(self.history[index + 1].clone());
+            }
+            Some(_) => {
+                self.history_index = None;
+                let draft = std::mem::take(&mut self.draft);
+                self.set_input(draft);
+            }
+        }
+    }
+
+    // Completes the last component of the path before the cursor with the
+    // entries of its directory. When several entries match, it completes as
+    // far as they agree. A single matching directory gets a trailing separator.
+    pub fn complete_path(&mut self) {
+        let before = &self.input[..self.byte_idx(self.cursor)];
+        let (dir, prefix) = match before.rfind(MAIN_SEPARATOR) {
+            Some(i) => before.split_at(i + 1),
+            None => ("", before),
+        };
+
+        let read_dir = match std::fs::read_dir(if dir.is_empty() { "." } else { dir }) {
+            Ok(read_dir) => read_dir,
+            Err(_) => return,
+        };
+
+        let mut matches: Vec<(String, bool)> = read_dir
+            .filter_map(Result::ok)
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let is_dir = entry.path().is_dir();
+                Some((name, is_dir))
+            })
+            .filter(|(name, _)| name.starts_with(prefix))
+            // Hidden entries are only offered when asked for with a ".".
+            .filter(|(name, _)| prefix.starts_with('.') || !name.starts_with('.'))
+            .collect();
+        matches.sort();
+
+        let completion = match matches.as_slice() {
+            [] => return,
+            [(name, true)] => format!("{}{}", name, MAIN_SEPARATOR),
+            [(name, false)] => name.clone(),
+            [(first, _), rest @ ..] => rest.iter().fold(first.clone(), |common, (name, _)| {
+                common_prefix(&common, name).to_string()
+            }),
+        };
+
+        let completed = format!("{}{}", dir, completion);
+        let after = self.input[self.byte_idx(self.cursor)..].to_string();
+        self.cursor = completed.chars().count();
+        self.input = completed + &after;
+    }
+}
+
+// The longest prefix two strings share, ending on a char boundary.
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let end = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, x), y)| x != y)
+        .map_or(a.len().min(b.len()), |((i, _), _)| i);
+    &a[..end]
+}
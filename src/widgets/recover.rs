@@ -0,0 +1,31 @@
+use crossterm::event::KeyCode;
+
+use strum_macros::Display;
+use strum_macros::*;
+
+use crate::swap::Swap;
+
+use super::diff_dialog::{DialogChoice, DiffDialog};
+
+#[derive(Debug, Display, Clone, Copy, EnumIter, EnumCount, PartialEq, Eq)]
+pub enum RecoverChoice {
+    Recover,
+    Diff,
+    Discard,
+    Ignore,
+}
+
+impl DialogChoice for RecoverChoice {
+    fn for_key(keycode: KeyCode) -> Option<RecoverChoice> {
+        match keycode {
+            KeyCode::Char('r') => Some(RecoverChoice::Recover),
+            KeyCode::Char('d') => Some(RecoverChoice::Diff),
+            KeyCode::Char('x') => Some(RecoverChoice::Discard),
+            KeyCode::Char('i') | KeyCode::Esc => Some(RecoverChoice::Ignore),
+            _ => None,
+        }
+    }
+}
+
+/// RecoverWindow asks what to do with the swap file found for a file that was opened.
+pub type RecoverWindow = DiffDialog<RecoverChoice, Swap>;